@@ -1,69 +1,655 @@
-// Counts 1- and 3-number gaps.
-fn count_gaps(input: &[i32]) -> (u32, u32) {
-    let mut gap1 = 0;
-    let mut gap3 = 0;
+use std::collections::BTreeMap;
 
-    for i in 1..input.len() {
-        let delta = input[i] - input[i - 1];
-        if delta > 3 {
-            panic!("nope");
+// Which adapters can follow each other, where the chain starts and where it ends.
+#[derive(Debug, Clone)]
+struct Rules {
+    // Allowed joltage differences between neighbours in a chain.
+    min_gap: i32,
+    max_gap: i32,
+    // Joltage of the outlet at the start of every chain.
+    outlet: i32,
+    // The device is rated this much above the largest adapter.
+    device_offset: i32,
+    // weights[gap - min_gap] is the weight of a gap, the weight of a chain is the sum over its
+    // gaps. Used for finding the shortest and longest chains.
+    weights: Vec<u64>,
+}
+
+impl Default for Rules {
+    // The puzzle's rules, with every gap weighing 1 so chain weights count links.
+    fn default() -> Rules {
+        Rules {
+            min_gap: 1,
+            max_gap: 3,
+            outlet: 0,
+            device_offset: 3,
+            weights: vec![1; 3],
+        }
+    }
+}
+
+impl Rules {
+    // Every gap in the range gets a weight, so the range can't be too wide.
+    const MAX_GAPS: i32 = 1 << 20;
+
+    // Applies a "name=value" option: gaps=MIN-MAX, outlet=N, device=N or weights=W,W,...
+    // Changing the gaps resets the weights to 1. The range can hold at most 2^20 gaps.
+    fn set(&mut self, option: &str) -> Result<(), String> {
+        let (name, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=VALUE: {}", option))?;
+        let bad_value = || format!("bad value for {}: {}", name, value);
+        let number = |s: &str| s.parse::<i32>().map_err(|_| bad_value());
+        match name {
+            "gaps" => {
+                let (min, max) = value.split_once('-').ok_or_else(bad_value)?;
+                let (min, max) = (number(min)?, number(max)?);
+                if min < 0 || min > max || max - min >= Rules::MAX_GAPS {
+                    return Err(bad_value());
+                }
+                self.min_gap = min;
+                self.max_gap = max;
+                self.weights = vec![1; (max - min + 1) as usize];
+            }
+            "outlet" => self.outlet = number(value)?,
+            "device" => {
+                self.device_offset = number(value)?;
+                if self.device_offset < 0 {
+                    return Err(bad_value());
+                }
+            }
+            "weights" => {
+                let weights = value
+                    .split(',')
+                    .map(|w| w.parse::<u64>().map_err(|_| bad_value()))
+                    .collect::<Result<Vec<u64>, String>>()?;
+                if weights.len() != self.weights.len() {
+                    return Err(format!(
+                        "expected {} weights for gaps {}-{}",
+                        self.weights.len(),
+                        self.min_gap,
+                        self.max_gap
+                    ));
+                }
+                self.weights = weights;
+            }
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    fn allows(&self, gap: i32) -> bool {
+        gap >= self.min_gap && gap <= self.max_gap
+    }
+
+    fn weight(&self, gap: i32) -> u64 {
+        self.weights[(gap - self.min_gap) as usize]
+    }
+
+    // Sorted joltages of the outlet, the adapters and the device, in that order. Fails if the
+    // device's joltage doesn't fit into an i32.
+    fn joltages(&self, adapters: &[i32]) -> Result<Vec<i32>, String> {
+        let mut sorted = adapters.to_vec();
+        sorted.sort_unstable();
+        let max = sorted
+            .last()
+            .map_or(self.outlet, |&max| max.max(self.outlet));
+        let device = max.checked_add(self.device_offset).ok_or_else(|| {
+            format!(
+                "device joltage {} + {} is too large",
+                max, self.device_offset
+            )
+        })?;
+        let mut joltages = vec![self.outlet];
+        joltages.extend(sorted);
+        joltages.push(device);
+        Ok(joltages)
+    }
+
+    // Counts the gaps in the chain using every adapter. Returns the first two neighbours that
+    // are too close or too far apart if there are any.
+    fn gap_histogram(&self, joltages: &[i32]) -> Result<BTreeMap<i32, usize>, (i32, i32)> {
+        let mut histogram = BTreeMap::new();
+        for pair in joltages.windows(2) {
+            let gap = match pair[1].checked_sub(pair[0]) {
+                Some(gap) if self.allows(gap) => gap,
+                _ => return Err((pair[0], pair[1])),
+            };
+            *histogram.entry(gap).or_insert(0) += 1;
+        }
+        Ok(histogram)
+    }
+}
+
+// Unsigned integer of any size, just enough of one to count and pick arrangements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BigUint {
+    // Base 2^32 digits, least significant first, no trailing zeros.
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_u64(n: u64) -> BigUint {
+        let mut big = BigUint {
+            digits: vec![n as u32, (n >> 32) as u32],
+        };
+        big.normalize();
+        big
+    }
+
+    fn normalize(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn add_assign(&mut self, other: &BigUint) {
+        let mut carry = 0u64;
+        for i in 0..self.digits.len().max(other.digits.len()) {
+            if i == self.digits.len() {
+                self.digits.push(0);
+            }
+            let sum = self.digits[i] as u64 + *other.digits.get(i).unwrap_or(&0) as u64 + carry;
+            self.digits[i] = sum as u32;
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            self.digits.push(carry as u32);
+        }
+    }
+
+    // Panics if other is larger.
+    fn sub_assign(&mut self, other: &BigUint) {
+        assert!(*self >= *other, "BigUint underflow");
+        let mut borrow = 0i64;
+        for i in 0..self.digits.len() {
+            let diff = self.digits[i] as i64 - *other.digits.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = if diff < 0 { 1 } else { 0 };
+            self.digits[i] = diff.rem_euclid(1 << 32) as u32;
+        }
+        self.normalize();
+    }
+
+    // Divides in place by a small number and returns the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for digit in self.digits.iter_mut().rev() {
+            let cur = (rem << 32) | *digit as u64;
+            *digit = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        self.normalize();
+        rem as u32
+    }
+
+    // Uniformly random number in 0..self, by drawing numbers with as many bits until one fits.
+    fn random_below(&self, rng: &mut Rng) -> BigUint {
+        let top = *self.digits.last().expect("random_below(0)");
+        let mask = u32::MAX >> top.leading_zeros();
+        loop {
+            let mut digits = self
+                .digits
+                .iter()
+                .map(|_| rng.next_u64() as u32)
+                .collect::<Vec<u32>>();
+            *digits.last_mut().unwrap() &= mask;
+            let mut candidate = BigUint { digits };
+            candidate.normalize();
+            if candidate < *self {
+                return candidate;
+            }
+        }
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> std::cmp::Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for BigUint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Chunks of 9 decimal digits, least significant first.
+        let mut chunks = vec![];
+        let mut n = self.clone();
+        while !n.is_zero() {
+            chunks.push(n.div_rem_small(1_000_000_000));
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+// xorshift64*, good enough for picking test chains and reproducible from a seed.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // xorshift gets stuck at 0, so the state must never be 0.
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+// All ways of chaining adapters from the first joltage (the outlet) to the last (the device).
+struct Chains {
+    rules: Rules,
+    // Outlet first, device last, see Rules::joltages().
+    joltages: Vec<i32>,
+    // Indices of the adapters that can follow each adapter, in increasing order.
+    next: Vec<Vec<usize>>,
+    // Number of ways to get from each adapter to the device.
+    ways: Vec<BigUint>,
+}
+
+impl Chains {
+    fn new(rules: &Rules, adapters: &[i32]) -> Result<Chains, String> {
+        let joltages = rules.joltages(adapters)?;
+        let next = (0..joltages.len())
+            .map(|i| {
+                // Joltages far apart, e.g., the outlet at i32::MIN, overflow an i32 gap.
+                (i + 1..joltages.len())
+                    .take_while(|&j| {
+                        joltages[j]
+                            .checked_sub(joltages[i])
+                            .is_some_and(|gap| gap <= rules.max_gap)
+                    })
+                    .filter(|&j| rules.allows(joltages[j] - joltages[i]))
+                    .collect::<Vec<usize>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+        let mut ways = vec![BigUint::from_u64(0); joltages.len()];
+        for i in (0..joltages.len()).rev() {
+            if i + 1 == joltages.len() {
+                ways[i] = BigUint::from_u64(1);
+                continue;
+            }
+            let mut sum = BigUint::from_u64(0);
+            for &j in next[i].iter() {
+                sum.add_assign(&ways[j]);
+            }
+            ways[i] = sum;
+        }
+        Ok(Chains {
+            rules: rules.clone(),
+            joltages,
+            next,
+            ways,
+        })
+    }
+
+    // The valid chain with the smallest total weight and that weight. Ties go to the
+    // lexicographically first chain. Weights are summed as u128, which no chain of u64 weights
+    // can overflow.
+    fn shortest(&self) -> Option<(Vec<i32>, u128)> {
+        self.extreme(|new, best| new < best)
+    }
+
+    // The valid chain with the largest total weight and that weight.
+    fn longest(&self) -> Option<(Vec<i32>, u128)> {
+        self.extreme(|new, best| new > best)
+    }
+
+    fn extreme(&self, better: fn(u128, u128) -> bool) -> Option<(Vec<i32>, u128)> {
+        let len = self.joltages.len();
+        // Best weight from each adapter to the device and the adapter to go to for it.
+        let mut best: Vec<Option<(u128, usize)>> = vec![None; len];
+        for i in (0..len.saturating_sub(1)).rev() {
+            for &j in self.next[i].iter() {
+                let rest = match (j + 1 == len, best[j]) {
+                    (true, _) => 0,
+                    (false, Some((rest, _))) => rest,
+                    (false, None) => continue,
+                };
+                let weight = rest + self.rules.weight(self.joltages[j] - self.joltages[i]) as u128;
+                if best[i].is_none_or(|(b, _)| better(weight, b)) {
+                    best[i] = Some((weight, j));
+                }
+            }
+        }
+        let (weight, _) = best.first().cloned().flatten()?;
+        let mut chain = vec![self.joltages[0]];
+        let mut node = 0;
+        while let Some((_, next)) = best[node] {
+            chain.push(self.joltages[next]);
+            node = next;
+        }
+        Some((chain, weight))
+    }
+
+    fn count(&self) -> BigUint {
+        self.ways
+            .first()
+            .cloned()
+            .unwrap_or_else(|| BigUint::from_u64(0))
+    }
+
+    // Arrangements in lexicographic order, computed as they are asked for.
+    fn iter(&self) -> Arrangements<'_> {
+        let mut arrangements = Arrangements {
+            chains: self,
+            nodes: vec![],
+            choices: vec![],
+        };
+        if !self.count().is_zero() {
+            arrangements.nodes.push(0);
+            arrangements.descend();
+        }
+        arrangements
+    }
+
+    // A random arrangement, every one equally likely. Walks from the outlet and picks each next
+    // adapter with probability proportional to the number of ways to finish from there.
+    fn sample(&self, rng: &mut Rng) -> Option<Vec<i32>> {
+        let count = self.count();
+        if count.is_zero() {
+            return None;
         }
-        if delta == 1 {
-            gap1 += 1;
-        } else if delta == 3 {
-            gap3 += 1;
+        let mut pick = count.random_below(rng);
+        let mut node = 0;
+        let mut chain = vec![self.joltages[0]];
+        while node + 1 != self.joltages.len() {
+            for &j in self.next[node].iter() {
+                if pick < self.ways[j] {
+                    node = j;
+                    break;
+                }
+                pick.sub_assign(&self.ways[j]);
+            }
+            chain.push(self.joltages[node]);
+        }
+        Some(chain)
+    }
+}
+
+// Iterator over arrangements, see Chains::iter().
+struct Arrangements<'a> {
+    chains: &'a Chains,
+    // The current arrangement, empty when done.
+    nodes: Vec<usize>,
+    // choices[i] is the index in chains.next[nodes[i]] of nodes[i + 1].
+    choices: Vec<usize>,
+}
+
+impl<'a> Arrangements<'a> {
+    // Extends the current arrangement with the smallest adapters that still reach the device.
+    fn descend(&mut self) {
+        let chains = self.chains;
+        let mut node = *self.nodes.last().unwrap();
+        while node + 1 != chains.joltages.len() {
+            let choice = chains.next[node]
+                .iter()
+                .position(|&j| !chains.ways[j].is_zero())
+                .unwrap();
+            node = chains.next[node][choice];
+            self.choices.push(choice);
+            self.nodes.push(node);
         }
     }
-    (gap1, gap3)
 }
 
-fn part1(input: &[i32]) {
-    let (gap1, gap3) = dbg!(count_gaps(input));
-    dbg!(gap1 * gap3);
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<i32>;
+
+    fn next(&mut self) -> Option<Vec<i32>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let chains = self.chains;
+        let current = self.nodes.iter().map(|&i| chains.joltages[i]).collect();
+
+        // Move to the next arrangement: change the last choice that has a larger alternative.
+        loop {
+            self.nodes.pop();
+            let choice = match self.choices.pop() {
+                Some(choice) => choice,
+                None => break,
+            };
+            let parent = *self.nodes.last().unwrap();
+            let alternative = chains.next[parent]
+                .iter()
+                .enumerate()
+                .skip(choice + 1)
+                .find(|(_, &j)| !chains.ways[j].is_zero());
+            if let Some((choice, &j)) = alternative {
+                self.choices.push(choice);
+                self.nodes.push(j);
+                self.descend();
+                break;
+            }
+        }
+
+        Some(current)
+    }
 }
 
-fn part2(input: &[i32]) {
-    let mut prev = [0u64; 3];
-    let mut next = [0u64; 3];
-    let numbers = input
-        .iter()
-        .cloned()
-        .collect::<std::collections::HashSet<i32>>();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // At 0 we have exactly 1 path for getting to 0.
-    prev[0] = 1;
-    prev[1] = 0;
-    prev[2] = 0;
+    const SIMPLE: [i32; 11] = [16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+
+    fn chains(adapters: &[i32]) -> Chains {
+        Chains::new(&Rules::default(), adapters).unwrap()
+    }
 
-    let max = numbers.iter().max().unwrap().clone() as usize;
+    #[test]
+    fn arrangements_are_listed_and_sampled() {
+        let chains = chains(&SIMPLE);
+        assert_eq!(chains.count().to_string(), "8");
+        let all = chains.iter().collect::<Vec<Vec<i32>>>();
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+        assert_eq!(all[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
 
-    for i in 1..=max {
-        if numbers.contains(&(i as i32)) {
-            // path where we take i
-            next[0] = prev[0] + prev[1] + prev[2];
-        } else {
-            next[0] = 0;
+        let mut rng = Rng::new(1);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..200 {
+            let chain = chains.sample(&mut rng).unwrap();
+            assert!(all.contains(&chain));
+            seen.insert(chain);
         }
-        // paths where we don't take i
-        next[1] = prev[0];
-        next[2] = prev[1];
-        dbg!(i, prev, next);
+        assert_eq!(seen.len(), 8);
+        // The one seed that would make the state 0 doesn't.
+        assert_ne!(Rng::new(0x9e37_79b9_7f4a_7c15).next_u64(), 0);
+
+        let none = Chains::new(&Rules::default(), &[5]).unwrap();
+        assert!(none.count().is_zero());
+        assert_eq!(none.iter().next(), None);
+        assert_eq!(none.sample(&mut rng), None);
+    }
+
+    #[test]
+    fn long_chains_dont_overflow() {
+        let adapters = (1..=100).collect::<Vec<i32>>();
+        let chains = chains(&adapters);
+        assert_eq!(chains.count().to_string(), "180396380815100901214157639");
+        let mut n = chains.count();
+        n.sub_assign(&BigUint::from_u64(1 << 40));
+        n.add_assign(&BigUint::from_u64(1 << 40));
+        assert_eq!(n, chains.count());
+        let first = chains.iter().next().unwrap();
+        assert_eq!(first.len(), 102);
+    }
+
+    #[test]
+    fn rules_are_configurable() {
+        let rules = Rules::default();
+        let joltages = rules.joltages(&SIMPLE).unwrap();
+        let histogram = rules.gap_histogram(&joltages).unwrap();
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![(1, 7), (3, 5)]
+        );
+        let chains = Chains::new(&rules, &SIMPLE).unwrap();
+        assert_eq!(
+            chains.shortest(),
+            Some((vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22], 9))
+        );
+        assert_eq!(chains.longest().map(|(_, w)| w), Some(12));
+
+        // Gaps of 3 cost more than three gaps of 1, so the longest chain by links is shortest.
+        let mut rules = Rules::default();
+        rules.set("weights=1,2,4").unwrap();
+        let chains = Chains::new(&rules, &SIMPLE).unwrap();
+        assert_eq!(chains.shortest().map(|(c, _)| c.len()), Some(13));
+
+        let mut rules = Rules::default();
+        rules.set("gaps=1-2").unwrap();
+        rules.set("outlet=-2").unwrap();
+        rules.set("device=1").unwrap();
+        let joltages = rules.joltages(&[0, 1, 3]).unwrap();
+        assert_eq!(joltages, vec![-2, 0, 1, 3, 4]);
+        assert_eq!(rules.gap_histogram(&joltages).unwrap().len(), 2);
+        assert_eq!(rules.gap_histogram(&[0, 3]), Err((0, 3)));
+        let chains = Chains::new(&rules, &[0, 1, 3]).unwrap();
+        assert_eq!(chains.count().to_string(), "1");
+        assert_eq!(chains.longest(), Some((joltages, 4)));
+
+        assert!(rules.set("weights=1,2,3").is_err());
+        assert!(rules.set("gaps=3-1").is_err());
+        assert!(rules.set("colour=red").is_err());
+        let none = Chains::new(&rules, &[10]).unwrap();
+        assert_eq!(none.shortest(), None);
+    }
+
+    #[test]
+    fn extreme_values_dont_overflow() {
+        let mut rules = Rules::default();
+        let max = u64::MAX.to_string();
+        rules
+            .set(&format!("weights={},{},{}", max, max, max))
+            .unwrap();
+        let chains = Chains::new(&rules, &SIMPLE).unwrap();
+        assert_eq!(
+            chains.longest().map(|(_, w)| w),
+            Some(12 * u64::MAX as u128)
+        );
 
-        prev = next;
+        let mut rules = Rules::default();
+        rules.set(&format!("outlet={}", i32::MIN)).unwrap();
+        let joltages = rules.joltages(&[i32::MAX - 3]).unwrap();
+        assert_eq!(joltages, vec![i32::MIN, i32::MAX - 3, i32::MAX]);
+        assert_eq!(
+            rules.gap_histogram(&joltages),
+            Err((i32::MIN, i32::MAX - 3))
+        );
+        let chains = Chains::new(&rules, &[i32::MAX - 3]).unwrap();
+        assert!(chains.count().is_zero());
+        assert!(Chains::new(&rules, &[i32::MAX - 2]).is_err());
+        let mut rules = Rules::default();
+        assert!(rules.set(&format!("gaps=0-{}", i32::MAX)).is_err());
+        rules
+            .set(&format!("gaps={}-{}", i32::MAX - 1, i32::MAX))
+            .unwrap();
+        assert_eq!(rules.weights.len(), 2);
     }
-    dbg!(next);
-    dbg!(next.iter().sum::<u64>());
 }
 
+// Usage: day10 [FILE [OPTION...] [list N | sample [SEED]]]
+//
+// OPTIONs change the rules, see Rules::set(). Without a mode, prints the gaps in the chain using
+// every adapter, the number of arrangements and the shortest and longest ones.
+// * list N: print the first N arrangements in lexicographic order.
+// * sample [SEED]: print a random arrangement.
 fn main() {
-    let contents = std::fs::read_to_string("input/10.txt").expect("read failed");
-    let mut input = contents
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/10.txt"));
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let adapters = contents
         .lines()
         .map(|line| line.parse::<i32>().unwrap())
         .collect::<Vec<i32>>();
-    input.push(0);
-    input.sort();
-    input.push(input.last().unwrap() + 3);
-    // part1(&input.as_slice());
-    part2(&input.as_slice());
+    let mut rules = Rules::default();
+    let mut args = std::env::args().skip(2).peekable();
+    while let Some(option) = args.next_if(|arg| arg.contains('=')) {
+        if let Err(e) = rules.set(&option) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    let chains = match Chains::new(&rules, &adapters) {
+        Ok(chains) => chains,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let to_string = |chain: &[i32]| {
+        let strs = chain.iter().map(|j| j.to_string()).collect::<Vec<String>>();
+        strs.join(" ")
+    };
+    match args.next().as_deref() {
+        Some("list") => {
+            let n = args.next().map_or(10, |n| n.parse().expect("bad count"));
+            for chain in chains.iter().take(n) {
+                println!("{}", to_string(&chain));
+            }
+        }
+        Some("sample") => {
+            let seed = args.next().map_or_else(
+                || {
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos() as u64
+                },
+                |s| s.parse().expect("bad seed"),
+            );
+            match chains.sample(&mut Rng::new(seed)) {
+                Some(chain) => println!("{}", to_string(&chain)),
+                None => println!("no arrangements"),
+            }
+        }
+        _ => {
+            match rules.gap_histogram(&chains.joltages) {
+                Ok(histogram) => {
+                    for (gap, count) in histogram.iter() {
+                        println!("gap {}: {}", gap, count);
+                    }
+                }
+                Err((a, b)) => println!("can't chain every adapter: {} -> {}", a, b),
+            }
+            println!("arrangements: {}", chains.count());
+            if let Some((chain, weight)) = chains.shortest() {
+                println!("shortest ({}): {}", weight, to_string(&chain));
+            }
+            if let Some((chain, weight)) = chains.longest() {
+                println!("longest ({}): {}", weight, to_string(&chain));
+            }
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 type State = Vec<Vec<char>>;
 
 fn parse_state(lines: &str) -> State {
@@ -13,82 +15,396 @@ fn parse_state(lines: &str) -> State {
 // 2) If a seat is occupied (#) and four or more seats adjacent to it are also occupied, the seat
 //    becomes empty.
 
-fn count_occupied_neighbors(state: &State, row: isize, col: isize) -> u8 {
-    let mut cnt = 0;
-    for r in (row - 1)..=(row + 1) {
-        if r < 0 || r >= (state.len() as isize) {
-            continue;
+// Rules for part 2:
+// * count in each of 8 directions until you hit a chair
+// * if occupied: 5 or more visible occupied seats -> seat becomes empty.
+// * if empty: no visible occupied seats -> seat becomes occupied
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Neighbourhood {
+    // The 8 cells around a seat.
+    Adjacent,
+    // The first seat in each of the 8 directions.
+    LineOfSight,
+}
+
+// Both parts' rules and variants of them.
+#[derive(Debug, Clone)]
+struct Rules {
+    neighbourhood: Neighbourhood,
+    // An occupied seat empties when at least this many neighbours are occupied.
+    tolerance: usize,
+    // How many cells away a seat can see, None for no limit. Only for LineOfSight.
+    max_distance: Option<usize>,
+    floor: char,
+    empty: char,
+    occupied: char,
+}
+
+impl Rules {
+    fn part1() -> Rules {
+        Rules {
+            neighbourhood: Neighbourhood::Adjacent,
+            tolerance: 4,
+            ..Rules::part2()
         }
-        for c in (col - 1)..=(col + 1) {
-            if c < 0 || c >= (state[0].len() as isize) {
-                continue;
+    }
+
+    fn part2() -> Rules {
+        Rules {
+            neighbourhood: Neighbourhood::LineOfSight,
+            tolerance: 5,
+            max_distance: None,
+            floor: '.',
+            empty: 'L',
+            occupied: '#',
+        }
+    }
+
+    // Applies a "name=value" option: rules=part1|part2, mode=adjacent|sight, tolerance=N,
+    // distance=N or chars=FLOOR EMPTY OCCUPIED (e.g. "chars=.L#").
+    fn set(&mut self, option: &str) -> Result<(), String> {
+        let (name, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=VALUE: {}", option))?;
+        let bad_value = || format!("bad value for {}: {}", name, value);
+        match name {
+            "rules" => {
+                *self = match value {
+                    "part1" => Rules::part1(),
+                    "part2" => Rules::part2(),
+                    _ => return Err(bad_value()),
+                }
             }
-            if r == row && c == col {
-                continue;
+            "mode" => {
+                self.neighbourhood = match value {
+                    "adjacent" => Neighbourhood::Adjacent,
+                    "sight" => Neighbourhood::LineOfSight,
+                    _ => return Err(bad_value()),
+                }
             }
-            if state[r as usize][c as usize] == '#' {
-                cnt += 1;
+            "tolerance" => self.tolerance = value.parse().map_err(|_| bad_value())?,
+            "distance" => {
+                let distance = value.parse().map_err(|_| bad_value())?;
+                if distance == 0 {
+                    return Err(bad_value());
+                }
+                self.max_distance = Some(distance);
             }
+            "chars" => match value.chars().collect::<Vec<char>>()[..] {
+                [floor, empty, occupied]
+                    if floor != empty && floor != occupied && empty != occupied =>
+                {
+                    self.floor = floor;
+                    self.empty = empty;
+                    self.occupied = occupied;
+                }
+                _ => return Err(bad_value()),
+            },
+            _ => return Err(format!("unknown option: {}", name)),
         }
+        Ok(())
     }
-    cnt
-}
 
-// Rules for part 2:
-// * count in each of 8 directions until you hit a chair
-// * if occupied: 5 or more visible occupied seats -> seat becomes empty.
-// * if empty: no visible occupied seats -> seat becomes occupied
+    // How far seats can see.
+    fn reach(&self) -> Option<usize> {
+        match self.neighbourhood {
+            Neighbourhood::Adjacent => Some(1),
+            Neighbourhood::LineOfSight => self.max_distance,
+        }
+    }
 
-fn count_los_occupied_seats(state: &State, row: isize, col: isize) -> u8 {
-    let mut cnt = 0;
-    for dy in -1 ..= 1 {
-        for dx in -1 ..= 1 {
-            if dy == 0 && dx == 0 {
-                continue;
-            }
-            let mut i = 1;
-            loop {
-                let r = row + i * dy;
-                let c = col + i * dx;
-                if r < 0 || r >= (state.len() as isize) {
-                    break;
+    // Returns the position and character of the first cell that isn't floor or a seat.
+    fn check(&self, state: &State) -> Result<(), (usize, usize, char)> {
+        for (r, row) in state.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                if cell != self.floor && cell != self.empty && cell != self.occupied {
+                    return Err((r, c, cell));
                 }
-                if c < 0 || c >= (state[0].len() as isize) {
-                    break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The seats of a layout and which seats see each other, computed once so steps only look at
+// seats and their neighbour lists.
+struct SeatGraph {
+    rules: Rules,
+    // The layout the graph was built from, for turning seats back into a State.
+    layout: State,
+    // (row, col) of every seat, in reading order.
+    seats: Vec<(usize, usize)>,
+    // The neighbours of each seat.
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl SeatGraph {
+    // Expects a layout that passed Rules::check().
+    fn build(rules: &Rules, layout: &State) -> SeatGraph {
+        let mut ids = layout
+            .iter()
+            .map(|row| vec![None; row.len()])
+            .collect::<Vec<Vec<Option<usize>>>>();
+        let mut seats = vec![];
+        for (r, row) in layout.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                if cell != rules.floor {
+                    ids[r][c] = Some(seats.len());
+                    seats.push((r, c));
                 }
-                match state[r as usize][c as usize] {
-                    '#' => { cnt += 1; break; },
-                    'L' => { break; },
-                    _ => {},
+            }
+        }
+        let cell = |r: isize, c: isize| -> Option<Option<usize>> {
+            if r < 0 || c < 0 {
+                return None;
+            }
+            ids.get(r as usize)?.get(c as usize).cloned()
+        };
+
+        // Seeing is mutual, so looking in 4 of the 8 directions finds every pair.
+        let mut neighbours = vec![vec![]; seats.len()];
+        for (id, &(row, col)) in seats.iter().enumerate() {
+            for &(dy, dx) in [(0, 1), (1, -1), (1, 0), (1, 1)].iter() {
+                let mut i = 1;
+                while let Some(seen) = cell(row as isize + i * dy, col as isize + i * dx) {
+                    if let Some(other) = seen {
+                        neighbours[id].push(other);
+                        neighbours[other].push(id);
+                        break;
+                    }
+                    if rules.reach().is_some_and(|reach| i as usize >= reach) {
+                        break;
+                    }
+                    i += 1;
                 }
-                i += 1;
             }
         }
+        SeatGraph {
+            rules: rules.clone(),
+            layout: layout.clone(),
+            seats,
+            neighbours,
+        }
     }
-    cnt
 }
 
-// Do one step, return new state and whether anything changed.
-fn step_v2(state: &State) -> (State, bool) {
-    let mut out = state.clone();
-    let mut changed = false;
-    for row in 0..state.len() {
-        for col in 0..state[0].len() {
-            let cnt = count_los_occupied_seats(state, row as isize, col as isize);
-            match state[row][col] {
-                'L' if cnt == 0 => {
-                    out[row][col] = '#';
-                    changed = true;
+// Runs the rules on a SeatGraph. Only seats next to a seat that changed in the last step can
+// change in the next one, so only those are looked at.
+struct Simulation {
+    graph: SeatGraph,
+    occupied: Vec<bool>,
+    // Number of occupied neighbours of every seat, kept up to date as seats change.
+    occupied_neighbours: Vec<usize>,
+    // Seats to look at in the next step.
+    dirty: Vec<usize>,
+}
+
+impl Simulation {
+    fn new(graph: SeatGraph) -> Simulation {
+        let occupied = graph
+            .seats
+            .iter()
+            .map(|&(r, c)| graph.layout[r][c] == graph.rules.occupied)
+            .collect::<Vec<bool>>();
+        let occupied_neighbours = graph
+            .neighbours
+            .iter()
+            .map(|ns| ns.iter().filter(|&&n| occupied[n]).count())
+            .collect();
+        let dirty = (0..graph.seats.len()).collect();
+        Simulation {
+            graph,
+            occupied,
+            occupied_neighbours,
+            dirty,
+        }
+    }
+
+    // Does one step, returns the number of seats that changed.
+    fn step(&mut self) -> usize {
+        let flips = self
+            .dirty
+            .iter()
+            .cloned()
+            .filter(|&s| {
+                let count = self.occupied_neighbours[s];
+                if self.occupied[s] {
+                    count >= self.graph.rules.tolerance
+                } else {
+                    count == 0
                 }
-                '#' if cnt >= 5 => {
-                    out[row][col] = 'L';
-                    changed = true;
+            })
+            .collect::<Vec<usize>>();
+
+        let mut is_dirty = vec![false; self.occupied.len()];
+        self.dirty.clear();
+        for &s in flips.iter() {
+            self.occupied[s] = !self.occupied[s];
+            for &n in self.graph.neighbours[s].iter() {
+                if self.occupied[s] {
+                    self.occupied_neighbours[n] += 1;
+                } else {
+                    self.occupied_neighbours[n] -= 1;
+                }
+            }
+            for &n in std::iter::once(&s).chain(self.graph.neighbours[s].iter()) {
+                if !is_dirty[n] {
+                    is_dirty[n] = true;
+                    self.dirty.push(n);
                 }
-                _ => {}
             }
         }
+        flips.len()
+    }
+
+    fn occupied_count(&self) -> usize {
+        self.occupied.iter().filter(|&&o| o).count()
+    }
+
+    fn state(&self) -> State {
+        let rules = &self.graph.rules;
+        let mut state = self.graph.layout.clone();
+        for (&(r, c), &occupied) in self.graph.seats.iter().zip(self.occupied.iter()) {
+            state[r][c] = if occupied {
+                rules.occupied
+            } else {
+                rules.empty
+            };
+        }
+        state
+    }
+}
+
+// How a recorded simulation ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    // Nothing changes after this many steps.
+    Stable(usize),
+    // The state after start + period steps is the same as after start steps.
+    Oscillates { start: usize, period: usize },
+    // Gave up after this many steps.
+    StepLimit(usize),
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Outcome::Stable(steps) => write!(f, "stable after {} steps", steps),
+            Outcome::Oscillates { start, period } => {
+                write!(f, "oscillates with period {} from step {}", period, start)
+            }
+            Outcome::StepLimit(steps) => write!(f, "not stable after {} steps", steps),
+        }
     }
-    (out, changed)
+}
+
+// Every generation of a simulation.
+struct Timeline {
+    rules: Rules,
+    // generations[0] is the initial state, generations[i] the state after step i.
+    generations: Vec<State>,
+    // changes[i] is the number of seats that changed going to generations[i + 1].
+    changes: Vec<usize>,
+    // Number of occupied seats in every generation.
+    occupied: Vec<usize>,
+    outcome: Outcome,
+}
+
+impl Timeline {
+    // Steps until nothing changes, a state repeats or max_steps steps were done.
+    fn record(mut simulation: Simulation, max_steps: Option<usize>) -> Timeline {
+        // Occupied seats -> generation, for finding repeats.
+        let mut seen = HashMap::new();
+        seen.insert(simulation.occupied.clone(), 0);
+        let mut generations = vec![simulation.state()];
+        let mut changes = vec![];
+        let mut occupied = vec![simulation.occupied_count()];
+        let outcome = loop {
+            let steps = changes.len();
+            if max_steps.is_some_and(|max| steps >= max) {
+                break Outcome::StepLimit(steps);
+            }
+            let changed = simulation.step();
+            if changed == 0 {
+                break Outcome::Stable(steps);
+            }
+            changes.push(changed);
+            generations.push(simulation.state());
+            occupied.push(simulation.occupied_count());
+            if let Some(&start) = seen.get(&simulation.occupied) {
+                break Outcome::Oscillates {
+                    start,
+                    period: steps + 1 - start,
+                };
+            }
+            seen.insert(simulation.occupied.clone(), steps + 1);
+        };
+        Timeline {
+            rules: simulation.graph.rules.clone(),
+            generations,
+            changes,
+            occupied,
+            outcome,
+        }
+    }
+
+    // All generations one after the other, each with a heading.
+    fn to_flipbook(&self) -> String {
+        let mut out = String::new();
+        for (i, state) in self.generations.iter().enumerate() {
+            out += &format!("generation {}: {} occupied", i, self.occupied[i]);
+            if i > 0 {
+                out += &format!(", {} changed", self.changes[i - 1]);
+            }
+            out += "\n";
+            for row in state.iter() {
+                out.extend(row.iter());
+                out += "\n";
+            }
+            out += "\n";
+        }
+        out += &format!("{}\n", self.outcome);
+        out
+    }
+
+    // Writes every generation to DIR/frame_NNNNN.ppm, every cell as a scale x scale square.
+    // Returns the number of frames.
+    fn write_frames(&self, dir: &str, scale: usize) -> std::io::Result<usize> {
+        std::fs::create_dir_all(dir)?;
+        for (i, state) in self.generations.iter().enumerate() {
+            let path = std::path::Path::new(dir).join(format!("frame_{:05}.ppm", i));
+            std::fs::write(path, to_ppm(&self.rules, state, scale))?;
+        }
+        Ok(self.generations.len())
+    }
+}
+
+// A binary PPM image of a state. Floor is dark, empty seats green, occupied seats red.
+fn to_ppm(rules: &Rules, state: &State, scale: usize) -> Vec<u8> {
+    const FLOOR: [u8; 3] = [40, 40, 40];
+    const EMPTY: [u8; 3] = [60, 180, 75];
+    const OCCUPIED: [u8; 3] = [220, 50, 50];
+
+    let width = state.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut out = format!("P6\n{} {}\n255\n", width * scale, state.len() * scale).into_bytes();
+    for row in state.iter() {
+        let mut line = vec![];
+        for c in 0..width {
+            let colour = match row.get(c) {
+                Some(&cell) if cell == rules.occupied => OCCUPIED,
+                Some(&cell) if cell == rules.empty => EMPTY,
+                _ => FLOOR,
+            };
+            for _ in 0..scale {
+                line.extend_from_slice(&colour);
+            }
+        }
+        for _ in 0..scale {
+            out.extend_from_slice(&line);
+        }
+    }
+    out
 }
 
 fn print_state(state: &State) {
@@ -98,22 +414,233 @@ fn print_state(state: &State) {
     }
 }
 
-fn run(initial_state: &State) -> usize {
-    let mut state = initial_state.clone();
-    loop {
-        let result = step_v2(&state);
-        state = result.0;
-        if !result.1 { 
-            break;
+// Steps until nothing changes, the state repeats or max_steps steps were done. The neighbour
+// relation is symmetric, so the rules can only settle or flip between two states, and keeping the
+// state from two steps ago is enough to find the repeat. Timeline keeps every generation.
+fn run(rules: &Rules, initial_state: &State, max_steps: Option<usize>) -> usize {
+    let mut simulation = Simulation::new(SeatGraph::build(rules, initial_state));
+    let mut steps = 0;
+    let mut two_back = None;
+    let outcome = loop {
+        if max_steps.is_some_and(|max| steps >= max) {
+            break Outcome::StepLimit(steps);
         }
+        let before = simulation.occupied.clone();
+        if simulation.step() == 0 {
+            break Outcome::Stable(steps);
+        }
+        steps += 1;
+        if two_back.as_ref() == Some(&simulation.occupied) {
+            break Outcome::Oscillates {
+                start: steps - 2,
+                period: 2,
+            };
+        }
+        two_back = Some(before);
+    };
+    print_state(&simulation.state());
+    println!("{}", outcome);
+    simulation.occupied_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulation_works() {
+        let text = std::fs::read_to_string("input/11_simple.txt").unwrap();
+        let layout = parse_state(&text);
+        let mut simulation = Simulation::new(SeatGraph::build(&Rules::part2(), &layout));
+        assert_eq!(simulation.graph.seats.len(), 71);
+        // Top left corner sees right, down and diagonally down.
+        assert_eq!(simulation.graph.neighbours[0].len(), 3);
+        let mut steps = 0;
+        while simulation.step() > 0 {
+            steps += 1;
+            // The incremental counts match counting from scratch.
+            for (s, neighbours) in simulation.graph.neighbours.iter().enumerate() {
+                let count = neighbours
+                    .iter()
+                    .filter(|&&n| simulation.occupied[n])
+                    .count();
+                assert_eq!(simulation.occupied_neighbours[s], count);
+            }
+        }
+        assert_eq!(steps, 6);
+        assert_eq!(simulation.occupied_count(), 26);
+        let state = simulation.state();
+        assert_eq!(state[0].iter().collect::<String>(), "#.L#.L#.L#");
+    }
+
+    #[test]
+    fn rules_are_configurable() {
+        let text = std::fs::read_to_string("input/11_simple.txt").unwrap();
+        let layout = parse_state(&text);
+        let settle = |rules: &Rules, layout: &State| {
+            let mut simulation = Simulation::new(SeatGraph::build(rules, layout));
+            while simulation.step() > 0 {}
+            simulation
+        };
+        assert_eq!(settle(&Rules::part1(), &layout).occupied_count(), 37);
+
+        // Seeing at most one cell away is the same as looking at adjacent cells.
+        let mut rules = Rules::part2();
+        rules.set("tolerance=4").unwrap();
+        rules.set("distance=1").unwrap();
+        assert_eq!(settle(&rules, &layout).occupied_count(), 37);
+        // Row 6 is "..L.L.....", those two seats are two cells apart.
+        let sees = |rules: &Rules| {
+            let graph = SeatGraph::build(rules, &layout);
+            let a = graph.seats.iter().position(|&p| p == (6, 2)).unwrap();
+            let b = graph.seats.iter().position(|&p| p == (6, 4)).unwrap();
+            graph.neighbours[a].contains(&b)
+        };
+        assert!(!sees(&rules));
+        rules.set("distance=2").unwrap();
+        assert!(sees(&rules));
+
+        let mut rules = Rules::part1();
+        rules.set("chars= o@").unwrap();
+        let other = parse_state(&text.replace('.', " ").replace('L', "o"));
+        assert_eq!(rules.check(&other), Ok(()));
+        let settled = settle(&rules, &other);
+        assert_eq!(settled.occupied_count(), 37);
+        assert_eq!(settled.state()[0].iter().collect::<String>(), "@ @o o@ @@");
+        assert_eq!(rules.check(&layout), Err((0, 0, 'L')));
+
+        assert!(rules.set("mode=diagonal").is_err());
+        assert!(rules.set("chars=..#").is_err());
+        assert!(rules.set("distance=0").is_err());
+    }
+
+    #[test]
+    fn timelines_end_and_export() {
+        let text = std::fs::read_to_string("input/11_simple.txt").unwrap();
+        let layout = parse_state(&text);
+        let record = |rules: &Rules, layout: &State, max_steps| {
+            Timeline::record(Simulation::new(SeatGraph::build(rules, layout)), max_steps)
+        };
+        let timeline = record(&Rules::part2(), &layout, None);
+        assert_eq!(timeline.outcome, Outcome::Stable(6));
+        assert_eq!(timeline.generations.len(), 7);
+        assert_eq!(timeline.changes, vec![71, 64, 46, 35, 13, 5]);
+        assert_eq!(timeline.occupied[6], 26);
+        let timeline = record(&Rules::part2(), &layout, Some(2));
+        assert_eq!(timeline.outcome, Outcome::StepLimit(2));
+
+        // Two neighbours that can't stand each other at all.
+        let mut rules = Rules::part1();
+        rules.set("tolerance=1").unwrap();
+        let timeline = record(&rules, &parse_state("L.\nLL\n"), None);
+        assert_eq!(
+            timeline.outcome,
+            Outcome::Oscillates {
+                start: 0,
+                period: 2
+            }
+        );
+        assert_eq!(timeline.occupied, vec![0, 3, 0]);
+        // Plain runs stop too, back in the initial state.
+        assert_eq!(run(&rules, &parse_state("L.\nLL\n"), None), 0);
+        assert!(timeline.to_flipbook().starts_with(
+            "generation 0: 0 occupied\nL.\nLL\n\ngeneration 1: 3 occupied, 3 changed\n#.\n##\n"
+        ));
+
+        let ppm = to_ppm(&rules, &timeline.generations[1], 2);
+        let header = b"P6\n4 4\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 4 * 4 * 3);
+        // The second pixel of the first row is still the occupied top left seat.
+        assert_eq!(ppm[header.len() + 3..header.len() + 6], [220, 50, 50]);
     }
-    print_state(&state);
-    state.iter().fold(0, |cnt, line| line.iter().filter(|&&x| x == '#').count() + cnt)
 }
 
+// Usage: day11 [FILE [OPTION...] [timeline | flipbook | frames DIR [SCALE]]]
+//
+// Without OPTIONs, runs both parts. With OPTIONs, runs the part 2 rules changed by them, see
+// Rules::set(). The steps=N option stops simulations after N steps and doesn't change the rules.
+// The modes record every generation, which also finds oscillations, and use the part 2 rules
+// unless OPTIONs change them.
+// * timeline: print the number of occupied and changed seats in every generation.
+// * flipbook: print every generation.
+// * frames DIR [SCALE]: write every generation to DIR as PPM images, SCALE (default 4) pixels
+//   per cell.
 fn main() {
-    let contents = std::fs::read_to_string("input/11.txt").expect("read failed");
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/11.txt"));
+    let contents = std::fs::read_to_string(path).expect("read failed");
     let initial_state = parse_state(contents.as_str());
-    print_state(&initial_state);
-    dbg!(run(&initial_state));
+    let (options, mode): (Vec<String>, Vec<String>) =
+        std::env::args().skip(2).partition(|arg| arg.contains('='));
+    let mut max_steps = None;
+    let mut rules = Rules::part2();
+    let mut custom_rules = false;
+    for option in options.iter() {
+        let result = match option.strip_prefix("steps=") {
+            Some(steps) => steps
+                .parse()
+                .map(|steps| max_steps = Some(steps))
+                .map_err(|_| format!("bad value for steps: {}", steps)),
+            None => {
+                custom_rules = true;
+                rules.set(option)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    let all_rules = if custom_rules {
+        vec![rules]
+    } else {
+        vec![Rules::part1(), Rules::part2()]
+    };
+    for rules in all_rules.iter() {
+        if let Err((r, c, cell)) = rules.check(&initial_state) {
+            eprintln!("unknown cell {:?} at row {}, column {}", cell, r + 1, c + 1);
+            std::process::exit(1);
+        }
+    }
+
+    let record = |rules: &Rules| {
+        let simulation = Simulation::new(SeatGraph::build(rules, &initial_state));
+        Timeline::record(simulation, max_steps)
+    };
+    let rules = all_rules.last().unwrap();
+    match mode.iter().map(|s| s.as_str()).collect::<Vec<&str>>()[..] {
+        ["timeline"] => {
+            let timeline = record(rules);
+            for (i, occupied) in timeline.occupied.iter().enumerate() {
+                match i {
+                    0 => println!("generation 0: {} occupied", occupied),
+                    _ => println!(
+                        "generation {}: {} occupied, {} changed",
+                        i,
+                        occupied,
+                        timeline.changes[i - 1]
+                    ),
+                }
+            }
+            println!("{}", timeline.outcome);
+        }
+        ["flipbook"] => print!("{}", record(rules).to_flipbook()),
+        ["frames", dir] | ["frames", dir, _] => {
+            let scale = mode.get(2).map_or(4, |s| s.parse().expect("bad scale"));
+            let timeline = record(rules);
+            let frames = timeline.write_frames(dir, scale).expect("write failed");
+            println!("wrote {} frames, {}", frames, timeline.outcome);
+        }
+        [] => {
+            for rules in all_rules.iter() {
+                dbg!(run(rules, &initial_state, max_steps));
+            }
+        }
+        _ => {
+            eprintln!("unknown mode: {}", mode.join(" "));
+            std::process::exit(1);
+        }
+    }
 }
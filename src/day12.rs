@@ -3,120 +3,797 @@
 // L\d+
 // R\d+
 // F\d+
+// B\d+            move backward, the opposite of F
+// waypoint X Y    put the waypoint at (X, Y) on the map
+// repeat N        run the commands up to the matching "end" N times
+// end
+// checkpoint NAME remember where the ship is under NAME
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cmd {
+    Move(Direction, u32),
+    Left(u32),
+    Right(u32),
+    Forward(u32),
+    Backward(u32),
+    Waypoint(i64, i64),
+    Repeat(u32, Vec<Cmd>),
+    Checkpoint(String),
+}
+
+// An invalid line in a list of commands.
+#[derive(Debug, Clone, PartialEq)]
+struct ParseError {
+    // 1-based.
+    line: usize,
+    message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// +x is east
+// +y is north
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl Point {
+    fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    fn manhattan(&self) -> f64 {
+        self.x.abs() + self.y.abs()
+    }
+
+    fn plus(&self, other: Point, times: f64) -> Point {
+        Point::new(self.x + other.x * times, self.y + other.y * times)
+    }
+
+    // Rotates counterclockwise around the origin. Multiples of 90 degrees are exact.
+    fn rotate(&self, degrees: f64) -> Point {
+        let Point { x, y } = *self;
+        match degrees.rem_euclid(360.0) {
+            0.0 => Point::new(x, y),
+            90.0 => Point::new(-y, x),
+            180.0 => Point::new(-x, -y),
+            270.0 => Point::new(y, -x),
+            d => {
+                let (sin, cos) = d.to_radians().sin_cos();
+                Point::new(x * cos - y * sin, x * sin + y * cos)
+            }
+        }
+    }
+}
+
+// What happens to the heading or waypoint after a rotation that isn't a multiple of 90 degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rounding {
+    // Keep them as they are.
+    Keep,
+    // Round to the nearest integer.
+    Nearest,
+    // Round towards zero.
+    TowardZero,
+}
+
+impl Rounding {
+    fn parse(s: &str) -> Option<Rounding> {
+        match s {
+            "keep" => Some(Rounding::Keep),
+            "nearest" => Some(Rounding::Nearest),
+            "toward-zero" => Some(Rounding::TowardZero),
+            _ => None,
+        }
+    }
+
+    fn apply(self, p: Point) -> Point {
+        match self {
+            Rounding::Keep => p,
+            Rounding::Nearest => Point::new(p.x.round(), p.y.round()),
+            Rounding::TowardZero => Point::new(p.x.trunc(), p.y.trunc()),
+        }
+    }
+}
+
+impl Direction {
+    fn unit(self) -> Point {
+        match self {
+            Direction::North => Point::new(0.0, 1.0),
+            Direction::South => Point::new(0.0, -1.0),
+            Direction::East => Point::new(1.0, 0.0),
+            Direction::West => Point::new(-1.0, 0.0),
+        }
+    }
+}
+
+// A way of following the navigation instructions.
+trait Navigator: std::fmt::Debug {
+    // Repeat and Checkpoint are left to the caller, see Route::trace().
+    fn apply(&mut self, cmd: &Cmd);
+
+    fn ship(&self) -> Point;
+
+    // Where the waypoint is relative to the ship, if there is one.
+    fn waypoint(&self) -> Option<Point> {
+        None
+    }
+}
+
+// Part 1: N/S/E/W move the ship, L/R turn it and F/B move it the way it is facing or the other
+// way. Setting the waypoint turns the ship towards it.
 #[derive(Debug)]
-struct Cmd {
-    cmd: char,
-    arg: u32,
+struct HeadingShip {
+    ship: Point,
+    // Unit vector unless rounding changed it, starts facing east.
+    heading: Point,
+    rounding: Rounding,
+}
+
+impl HeadingShip {
+    fn new(rounding: Rounding) -> HeadingShip {
+        HeadingShip {
+            ship: Point::new(0.0, 0.0),
+            heading: Point::new(1.0, 0.0),
+            rounding,
+        }
+    }
+}
+
+impl Navigator for HeadingShip {
+    fn apply(&mut self, cmd: &Cmd) {
+        match *cmd {
+            Cmd::Move(d, arg) => self.ship = self.ship.plus(d.unit(), arg as f64),
+            Cmd::Left(arg) => self.heading = self.rounding.apply(self.heading.rotate(arg as f64)),
+            Cmd::Right(arg) => {
+                self.heading = self.rounding.apply(self.heading.rotate(-(arg as f64)))
+            }
+            Cmd::Forward(arg) => self.ship = self.ship.plus(self.heading, arg as f64),
+            Cmd::Backward(arg) => self.ship = self.ship.plus(self.heading, -(arg as f64)),
+            Cmd::Waypoint(x, y) => {
+                let to = Point::new(x as f64 - self.ship.x, y as f64 - self.ship.y);
+                let length = to.x.hypot(to.y);
+                if length > 0.0 {
+                    let heading = Point::new(to.x / length, to.y / length);
+                    self.heading = self.rounding.apply(heading);
+                }
+            }
+            Cmd::Repeat(..) | Cmd::Checkpoint(_) => {}
+        }
+    }
+
+    fn ship(&self) -> Point {
+        self.ship
+    }
 }
 
+// Part 2: N/S/E/W move the waypoint, L/R rotate it around the ship and F/B move the ship to the
+// waypoint (or away from it) that many times.
 #[derive(Debug)]
-struct State {
-    // +x is east
-    // +y is north
-    ship_x: i32,
-    ship_y: i32,
-    wp_x: i32,
-    wp_y: i32,
+struct WaypointShip {
+    ship: Point,
+    // Relative to the ship.
+    waypoint: Point,
+    rounding: Rounding,
 }
 
-impl Cmd {
-    fn parse(line: &str) -> Cmd {
-        let (cmd, arg_str) = line.split_at(1);
-        let arg = arg_str.parse::<u32>().unwrap();
-        Cmd {
-            cmd: cmd.chars().nth(0).unwrap(),
-            arg,
+impl WaypointShip {
+    fn new(rounding: Rounding) -> WaypointShip {
+        WaypointShip {
+            ship: Point::new(0.0, 0.0),
+            waypoint: Point::new(10.0, 1.0),
+            rounding,
         }
     }
 }
 
-impl State {
-    fn new() -> State {
-        State {
-            ship_x: 0,
-            ship_y: 0,
-            wp_x: 10,
-            wp_y: 1,
+impl Navigator for WaypointShip {
+    fn apply(&mut self, cmd: &Cmd) {
+        match *cmd {
+            Cmd::Move(d, arg) => self.waypoint = self.waypoint.plus(d.unit(), arg as f64),
+            // wp_x: 10, wp_y: 1
+            //
+            //     X    (-1, 10)
+            //      .
+            //      .
+            //      .
+            //      .              X   (10, 1)
+            //    [ship] ..........
+            Cmd::Left(arg) => self.waypoint = self.rounding.apply(self.waypoint.rotate(arg as f64)),
+            Cmd::Right(arg) => {
+                self.waypoint = self.rounding.apply(self.waypoint.rotate(-(arg as f64)))
+            }
+            Cmd::Forward(arg) => self.ship = self.ship.plus(self.waypoint, arg as f64),
+            Cmd::Backward(arg) => self.ship = self.ship.plus(self.waypoint, -(arg as f64)),
+            Cmd::Waypoint(x, y) => {
+                self.waypoint = Point::new(x as f64 - self.ship.x, y as f64 - self.ship.y)
+            }
+            Cmd::Repeat(..) | Cmd::Checkpoint(_) => {}
         }
     }
 
-    fn angle_to_d(angle: i32) -> (i32, i32) {
-        match angle {
-            0 => (0, 1),    // north
-            90 => (1, 0),   // east
-            180 => (0, -1), // south
-            270 => (-1, 0), // west
-            _ => {
-                panic!("invalid angle: {}", angle);
-            }
-        }
-    }
-
-    fn apply(self: &mut Self, cmd: &Cmd) {
-        let (dx, dy) = match cmd.cmd {
-            'N' => (0i32, 1i32),
-            'S' => (0, -1),
-            'E' => (1, 0),
-            'W' => (-1, 0),
-            'L' => {
-                // wp_x: 10, wp_y: 1
-                //
-                //     X    (-1, 10)
-                //      .
-                //      .
-                //      .
-                //      .              X   (10, 1)
-                //    [ship] ..........
-                assert_eq!(cmd.arg % 90, 0);
-                for _ in 0..(cmd.arg / 90) {
-                    let tmp = self.wp_x;
-                    self.wp_x = -self.wp_y;
-                    self.wp_y = tmp;
+    fn ship(&self) -> Point {
+        self.ship
+    }
+
+    fn waypoint(&self) -> Option<Point> {
+        Some(self.waypoint)
+    }
+}
+
+impl std::fmt::Display for Cmd {
+    // One line, without the commands in a repeat block.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Cmd::Move(d, arg) => {
+                let c = match d {
+                    Direction::North => 'N',
+                    Direction::South => 'S',
+                    Direction::East => 'E',
+                    Direction::West => 'W',
+                };
+                write!(f, "{}{}", c, arg)
+            }
+            Cmd::Left(arg) => write!(f, "L{}", arg),
+            Cmd::Right(arg) => write!(f, "R{}", arg),
+            Cmd::Forward(arg) => write!(f, "F{}", arg),
+            Cmd::Backward(arg) => write!(f, "B{}", arg),
+            Cmd::Waypoint(x, y) => write!(f, "waypoint {} {}", x, y),
+            Cmd::Repeat(n, _) => write!(f, "repeat {}", n),
+            Cmd::Checkpoint(name) => write!(f, "checkpoint {}", name),
+        }
+    }
+}
+
+impl Cmd {
+    // Parses a command that fits on one line, so not repeat.
+    fn parse(line: &str) -> Result<Cmd, String> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let number = |s: &str| {
+            s.parse::<i64>()
+                .map_err(|_| format!("not a number: {:?}", s))
+        };
+        match words[..] {
+            ["waypoint", x, y] => return Ok(Cmd::Waypoint(number(x)?, number(y)?)),
+            ["waypoint", ..] => return Err(String::from("expected waypoint X Y")),
+            ["checkpoint", name] => return Ok(Cmd::Checkpoint(name.to_string())),
+            ["checkpoint", ..] => return Err(String::from("expected checkpoint NAME")),
+            [_] => {}
+            _ => return Err(format!("unknown command: {:?}", line)),
+        }
+
+        let mut chars = line.chars();
+        let cmd = chars.next().unwrap();
+        let arg_str = chars.as_str();
+        let arg = arg_str
+            .parse::<u32>()
+            .map_err(|_| format!("bad argument: {:?}", line))?;
+        match cmd {
+            'N' => Ok(Cmd::Move(Direction::North, arg)),
+            'S' => Ok(Cmd::Move(Direction::South, arg)),
+            'E' => Ok(Cmd::Move(Direction::East, arg)),
+            'W' => Ok(Cmd::Move(Direction::West, arg)),
+            'L' => Ok(Cmd::Left(arg)),
+            'R' => Ok(Cmd::Right(arg)),
+            'F' => Ok(Cmd::Forward(arg)),
+            'B' => Ok(Cmd::Backward(arg)),
+            _ => Err(format!("unknown command: {:?}", line)),
+        }
+    }
+}
+
+// Most commands a file may run once repeat blocks are unrolled, see Route::trace().
+const MAX_UNROLLED: u64 = 1_000_000;
+
+// Parses one command per line, skipping blank lines. Checkpoint names must be unique and repeat
+// blocks can't unroll to more than MAX_UNROLLED commands.
+fn parse_file(text: &str) -> Result<Vec<Cmd>, ParseError> {
+    // Open repeat blocks: line, count, commands so far and how many commands they unroll to. The
+    // bottom one is the whole file.
+    let mut blocks: Vec<(usize, u32, Vec<Cmd>, u64)> = vec![(0, 1, vec![], 0)];
+    let mut checkpoints = std::collections::HashSet::new();
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: i + 1,
+            message,
+        };
+        let line = line.trim();
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        // Commands the new one unrolls to.
+        let mut unrolled = 1;
+        let cmd = match words[..] {
+            [] => continue,
+            ["repeat", n] => {
+                let n = n
+                    .parse::<u32>()
+                    .map_err(|_| error(format!("bad repeat count: {:?}", n)))?;
+                blocks.push((i + 1, n, vec![], 0));
+                continue;
+            }
+            ["repeat", ..] => return Err(error(String::from("expected repeat N"))),
+            ["end"] => {
+                if blocks.len() == 1 {
+                    return Err(error(String::from("end without repeat")));
                 }
-                (0, 0)
-            },
-            'R' => {
-                assert_eq!(cmd.arg % 90, 0);
-                for _ in 0..(cmd.arg / 90) {
-                    let tmp = self.wp_x;
-                    self.wp_x = self.wp_y;
-                    self.wp_y = -tmp;
+                let (start, n, cmds, inner) = blocks.pop().unwrap();
+                unrolled = inner.saturating_mul(n as u64);
+                if unrolled > MAX_UNROLLED {
+                    return Err(ParseError {
+                        line: start,
+                        message: format!("repeat unrolls to more than {} commands", MAX_UNROLLED),
+                    });
                 }
-                (0, 0)
-            },
-            'F' => (self.wp_x, self.wp_y),
-            _ => {
-                panic!("eek");
+                Cmd::Repeat(n, cmds)
+            }
+            _ => Cmd::parse(line).map_err(error)?,
+        };
+        if let Cmd::Checkpoint(name) = &cmd {
+            if !checkpoints.insert(name.clone()) {
+                return Err(error(format!("duplicate checkpoint: {}", name)));
+            }
+        }
+        let block = blocks.last_mut().unwrap();
+        block.2.push(cmd);
+        block.3 += unrolled;
+        if block.3 > MAX_UNROLLED {
+            return Err(error(format!(
+                "more than {} commands after unrolling",
+                MAX_UNROLLED
+            )));
+        }
+    }
+    let (line, _, cmds, _) = blocks.pop().unwrap();
+    if !blocks.is_empty() {
+        return Err(ParseError {
+            line,
+            message: String::from("repeat without end"),
+        });
+    }
+    Ok(cmds)
+}
+
+// Where the ship (and waypoint) were after a command.
+#[derive(Debug, Clone)]
+struct RoutePoint {
+    // The command that got there, None for the start.
+    cmd: Option<String>,
+    // Set if the command was a checkpoint.
+    checkpoint: Option<String>,
+    ship: Point,
+    waypoint: Option<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RouteStats {
+    // Largest Manhattan distance from the start and the step where it was reached.
+    furthest: f64,
+    furthest_step: usize,
+    // Bounding box of the ship's positions.
+    min: Point,
+    max: Point,
+    // Length of the ship's path, in straight lines between steps.
+    travelled: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    // points[0] is the start, points[i] is after the i-th command.
+    points: Vec<RoutePoint>,
+}
+
+impl Route {
+    fn trace(navigator: &mut dyn Navigator, cmds: &[Cmd]) -> Route {
+        let mut points = vec![RoutePoint {
+            cmd: None,
+            checkpoint: None,
+            ship: navigator.ship(),
+            waypoint: navigator.waypoint(),
+        }];
+        Route::trace_block(navigator, cmds, &mut points);
+        Route { points }
+    }
+
+    // Runs commands, unrolling repeat blocks.
+    fn trace_block(navigator: &mut dyn Navigator, cmds: &[Cmd], points: &mut Vec<RoutePoint>) {
+        for cmd in cmds.iter() {
+            if let Cmd::Repeat(n, block) = cmd {
+                for _ in 0..*n {
+                    Route::trace_block(navigator, block, points);
+                }
+                continue;
             }
+            navigator.apply(cmd);
+            points.push(RoutePoint {
+                cmd: Some(cmd.to_string()),
+                checkpoint: match cmd {
+                    Cmd::Checkpoint(name) => Some(name.clone()),
+                    _ => None,
+                },
+                ship: navigator.ship(),
+                waypoint: navigator.waypoint(),
+            });
+        }
+    }
+
+    // Steps where checkpoints were passed, in order. Checkpoints in repeat blocks show up once
+    // per repetition.
+    fn checkpoints(&self) -> Vec<(usize, &RoutePoint)> {
+        self.points
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| point.checkpoint.is_some())
+            .collect()
+    }
+
+    fn stats(&self) -> RouteStats {
+        let start = self.points[0].ship;
+        let mut stats = RouteStats {
+            furthest: 0.0,
+            furthest_step: 0,
+            min: start,
+            max: start,
+            travelled: 0.0,
         };
-        if cmd.cmd == 'F' {
-            self.ship_x += dx * cmd.arg as i32;
-            self.ship_y += dy * cmd.arg as i32;
-        } else {
-            self.wp_x += dx * cmd.arg as i32;
-            self.wp_y += dy * cmd.arg as i32;
+        for (step, point) in self.points.iter().enumerate() {
+            let p = point.ship;
+            let distance = Point::new(p.x - start.x, p.y - start.y).manhattan();
+            if distance > stats.furthest {
+                stats.furthest = distance;
+                stats.furthest_step = step;
+            }
+            stats.min = Point::new(stats.min.x.min(p.x), stats.min.y.min(p.y));
+            stats.max = Point::new(stats.max.x.max(p.x), stats.max.y.max(p.y));
+            if step > 0 {
+                let prev = self.points[step - 1].ship;
+                stats.travelled += (p.x - prev.x).hypot(p.y - prev.y);
+            }
         }
+        stats
     }
 }
 
-fn parse_file(lines: &str) -> Vec<Cmd> {
-    lines.lines().map(|line| Cmd::parse(line)).collect()
+// Quotes a CSV field if it has to be, e.g., for a checkpoint name with a comma.
+fn csv_quote(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
 }
 
-fn run(cmds: &Vec<Cmd>) -> i32 {
-    let mut state = State::new();
-    for cmd in cmds.iter() {
-        dbg!(&state);
-        state.apply(cmd);
+// Routes of several navigators as one CSV table, told apart by the navigator column.
+fn routes_to_csv(routes: &[(&str, Route)]) -> String {
+    let mut out = String::from("navigator,step,command,ship_x,ship_y,waypoint_x,waypoint_y\n");
+    for (name, route) in routes.iter() {
+        for (step, point) in route.points.iter().enumerate() {
+            let (wx, wy) = point.waypoint.map_or((String::new(), String::new()), |w| {
+                (w.x.to_string(), w.y.to_string())
+            });
+            out += &format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_quote(name),
+                step,
+                csv_quote(point.cmd.as_deref().unwrap_or("")),
+                point.ship.x,
+                point.ship.y,
+                wx,
+                wy
+            );
+        }
     }
-    dbg!(&state);
-    state.ship_x.abs() + state.ship_y.abs()
+    out
 }
 
+// Routes of several navigators as one SVG image: for each, a group named after the navigator
+// with the ship's path as a polyline and a line from the ship to the waypoint at every step.
+// North is up.
+fn routes_to_svg(routes: &[(&str, Route)]) -> String {
+    const COLOURS: [&str; 3] = ["navy", "crimson", "teal"];
+
+    let mut min = Point::new(0.0, 0.0);
+    let mut max = min;
+    // SVG's y axis points down.
+    let flip = |y: f64| 0.0 - y;
+    let mut extend = |p: Point| {
+        min = Point::new(min.x.min(p.x), min.y.min(p.y));
+        max = Point::new(max.x.max(p.x), max.y.max(p.y));
+    };
+    for (_, route) in routes.iter() {
+        for point in route.points.iter() {
+            extend(point.ship);
+            if let Some(w) = point.waypoint {
+                extend(point.ship.plus(w, 1.0));
+            }
+        }
+    }
+    let margin = ((max.x - min.x).max(max.y - min.y) / 20.0).max(1.0);
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min.x - margin,
+        flip(max.y) - margin,
+        max.x - min.x + 2.0 * margin,
+        max.y - min.y + 2.0 * margin
+    );
+    for (i, (name, route)) in routes.iter().enumerate() {
+        out += &format!("<g id=\"{}\">\n", name);
+        for point in route.points.iter() {
+            if let Some(w) = point.waypoint {
+                let to = point.ship.plus(w, 1.0);
+                out += &format!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"orange\" \
+                     vector-effect=\"non-scaling-stroke\"/>\n",
+                    point.ship.x,
+                    flip(point.ship.y),
+                    to.x,
+                    flip(to.y)
+                );
+            }
+        }
+        let coords = route
+            .points
+            .iter()
+            .map(|point| format!("{},{}", point.ship.x, flip(point.ship.y)))
+            .collect::<Vec<String>>();
+        out += &format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" \
+             vector-effect=\"non-scaling-stroke\"/>\n",
+            coords.join(" "),
+            COLOURS[i % COLOURS.len()]
+        );
+        out += "</g>\n";
+    }
+    out += "</svg>\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(navigator: &mut dyn Navigator, cmds: &[Cmd]) -> f64 {
+        Route::trace(navigator, cmds);
+        navigator.ship().manhattan()
+    }
+
+    #[test]
+    fn both_navigators_work() {
+        let text = std::fs::read_to_string("input/12_simple.txt").unwrap();
+        let cmds = parse_file(&text).unwrap();
+        let mut heading = HeadingShip::new(Rounding::Keep);
+        assert_eq!(run(&mut heading, &cmds), 25.0);
+        let mut waypoint = WaypointShip::new(Rounding::Keep);
+        assert_eq!(run(&mut waypoint, &cmds), 286.0);
+
+        // Turning left is turning right the other way round.
+        let mut a = WaypointShip::new(Rounding::Keep);
+        let mut b = WaypointShip::new(Rounding::Keep);
+        a.apply(&Cmd::parse("L90").unwrap());
+        b.apply(&Cmd::parse("R270").unwrap());
+        assert_eq!(a.waypoint, Point::new(-1.0, 10.0));
+        assert_eq!(a.waypoint, b.waypoint);
+    }
+
+    #[test]
+    fn any_angle_with_rounding() {
+        let cmds = parse_file("L45\nF10\n").unwrap();
+        let mut ship = HeadingShip::new(Rounding::Keep);
+        let distance = run(&mut ship, &cmds);
+        assert!((distance - 2.0 * 50f64.sqrt()).abs() < 1e-9);
+        // Both navigators round the turned vector: (0.71, 0.71) becomes (1, 1) or (0, 0).
+        let mut ship = HeadingShip::new(Rounding::Nearest);
+        assert_eq!(run(&mut ship, &cmds), 20.0);
+        assert_eq!(ship.heading, Point::new(1.0, 1.0));
+        let mut ship = HeadingShip::new(Rounding::TowardZero);
+        assert_eq!(run(&mut ship, &cmds), 0.0);
+        let mut ship = WaypointShip::new(Rounding::Nearest);
+        ship.waypoint = Point::new(1.0, 0.0);
+        assert_eq!(run(&mut ship, &cmds), 20.0);
+        assert_eq!(ship.waypoint, Point::new(1.0, 1.0));
+
+        let mut ship = WaypointShip::new(Rounding::Nearest);
+        ship.apply(&Cmd::parse("R30").unwrap());
+        // (10, 1) turned 30 degrees clockwise is (9.16, -4.13).
+        assert_eq!(ship.waypoint, Point::new(9.0, -4.0));
+        // And back again.
+        ship.apply(&Cmd::parse("R330").unwrap());
+        assert_eq!(ship.waypoint, Point::new(10.0, 1.0));
+    }
+
+    #[test]
+    fn routes_are_traced() {
+        let text = std::fs::read_to_string("input/12_simple.txt").unwrap();
+        let cmds = parse_file(&text).unwrap();
+        let route = Route::trace(&mut WaypointShip::new(Rounding::Keep), &cmds);
+        assert_eq!(route.points.len(), 6);
+        assert_eq!(route.points[5].ship, Point::new(214.0, -72.0));
+        assert_eq!(route.points[5].waypoint, Some(Point::new(4.0, -10.0)));
+        let stats = route.stats();
+        assert_eq!(
+            (stats.furthest, stats.furthest_step, stats.min, stats.max),
+            (286.0, 5, Point::new(0.0, -72.0), Point::new(214.0, 38.0))
+        );
+        let travelled = 10.0 * 101f64.sqrt() + 36.0 * 29f64.sqrt();
+        assert!((stats.travelled - travelled).abs() < 1e-9);
+        let heading = Route::trace(&mut HeadingShip::new(Rounding::Keep), &cmds);
+        let routes = [("waypoint", route), ("heading", heading)];
+        let csv = routes_to_csv(&routes);
+        assert_eq!(csv.matches("navigator,").count(), 1);
+        assert!(csv.starts_with(
+            "navigator,step,command,ship_x,ship_y,waypoint_x,waypoint_y\n\
+             waypoint,0,,0,0,10,1\n"
+        ));
+        assert!(csv.contains("waypoint,5,F11,214,-72,4,-10\n"));
+        assert!(csv.ends_with("heading,5,F11,17,-8,,\n"));
+        // Checkpoint names are free text.
+        let cmds = parse_file("F10\ncheckpoint a,\"b\"\n").unwrap();
+        let named = Route::trace(&mut HeadingShip::new(Rounding::Keep), &cmds);
+        let csv = routes_to_csv(&[("heading", named)]);
+        assert!(csv.ends_with("heading,2,\"checkpoint a,\"\"b\"\"\",10,0,,\n"));
+
+        let svg = routes_to_svg(&routes);
+        assert_eq!(svg.matches("<svg").count(), 1);
+        assert!(svg.contains("<polyline points=\"0,0 100,-10 100,-10 170,-38 170,-38 214,72\""));
+        assert!(svg.contains("<polyline points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+        assert_eq!(svg.matches("<g id=").count(), 2);
+        // Only the waypoint navigator has a waypoint to draw.
+        assert_eq!(svg.matches("<line").count(), 6);
+    }
+
+    #[test]
+    fn extended_commands() {
+        let text = "\
+            checkpoint start
+            repeat 2
+              F10
+              repeat 3
+                B1
+              end
+            end
+            waypoint -3 4
+            checkpoint there
+            F1
+            repeat 0
+              F100
+            end
+        ";
+        let cmds = parse_file(text).unwrap();
+        assert_eq!(cmds.len(), 6);
+        assert_eq!(
+            cmds[1],
+            Cmd::Repeat(
+                2,
+                vec![Cmd::Forward(10), Cmd::Repeat(3, vec![Cmd::Backward(1)])]
+            )
+        );
+        assert_eq!(cmds[2], Cmd::Waypoint(-3, 4));
+
+        let route = Route::trace(&mut WaypointShip::new(Rounding::Keep), &cmds);
+        // Twice 10 forward and 3 back to the (10, 1) waypoint.
+        let checkpoints = route.checkpoints();
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[1].0, 11);
+        assert_eq!(checkpoints[1].1.ship, Point::new(140.0, 14.0));
+        assert_eq!(checkpoints[1].1.waypoint, Some(Point::new(-143.0, -10.0)));
+        assert_eq!(route.points.last().unwrap().ship, Point::new(-3.0, 4.0));
+
+        // The ship turns towards the waypoint.
+        let mut ship = HeadingShip::new(Rounding::Keep);
+        let cmds = parse_file("F2\nwaypoint 2 3\nF5\n").unwrap();
+        Route::trace(&mut ship, &cmds);
+        assert_eq!(ship.ship, Point::new(2.0, 5.0));
+
+        let error = |text: &str| parse_file(text).unwrap_err().to_string();
+        assert_eq!(error("F10\nX3\n"), "line 2: unknown command: \"X3\"");
+        assert_eq!(error("F-1\n"), "line 1: bad argument: \"F-1\"");
+        assert_eq!(error("\nrepeat 2\nF1\n"), "line 2: repeat without end");
+        assert_eq!(error("end\n"), "line 1: end without repeat");
+        assert_eq!(
+            error("checkpoint a\ncheckpoint a\n"),
+            "line 2: duplicate checkpoint: a"
+        );
+        assert_eq!(error("waypoint 1\n"), "line 1: expected waypoint X Y");
+        assert_eq!(
+            error("F1\nrepeat 4000000000\nF1\nend\n"),
+            "line 2: repeat unrolls to more than 1000000 commands"
+        );
+        assert_eq!(
+            error("repeat 1000\nrepeat 1000\nrepeat 1000\nF1\nend\nend\nend\n"),
+            "line 1: repeat unrolls to more than 1000000 commands"
+        );
+        assert_eq!(
+            error("repeat 1000\nrepeat 1000\nF1\nend\nend\nF1\n"),
+            "line 6: more than 1000000 commands after unrolling"
+        );
+        // Blocks are checked on their own, even inside a repeat 0.
+        assert!(parse_file("repeat 0\nrepeat 4000000000\nF1\nend\nend\n").is_err());
+        assert!(parse_file("repeat 1000\nrepeat 999\nF1\nend\nend\n").is_ok());
+    }
+}
+
+// Usage: day12 [FILE [WORD...]]
+//
+// WORDs choose:
+// * the rounding for rotations by angles that aren't multiples of 90 degrees: keep (the
+//   default), nearest or toward-zero.
+// * which navigator to use: heading or waypoint. Both if not given.
+// * what to print: csv or svg with the routes of all chosen navigators in one document,
+//   statistics if not given.
 fn main() {
-    let contents = std::fs::read_to_string("input/12.txt").expect("read failed");
-    dbg!(&contents);
-    let cmds = dbg!(parse_file(contents.as_str()));
-    dbg!(run(&cmds));
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/12.txt"));
+    let mut rounding = Rounding::Keep;
+    let mut navigators = vec!["heading", "waypoint"];
+    let mut output = None;
+    for word in std::env::args().skip(2) {
+        match word.as_str() {
+            "heading" => navigators = vec!["heading"],
+            "waypoint" => navigators = vec!["waypoint"],
+            "csv" | "svg" => output = Some(word),
+            _ => {
+                rounding =
+                    Rounding::parse(&word).unwrap_or_else(|| panic!("unknown word: {}", word))
+            }
+        }
+    }
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let cmds = parse_file(contents.as_str()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let routes = navigators
+        .into_iter()
+        .map(|name| {
+            let mut navigator: Box<dyn Navigator> = match name {
+                "heading" => Box::new(HeadingShip::new(rounding)),
+                _ => Box::new(WaypointShip::new(rounding)),
+            };
+            (name, Route::trace(navigator.as_mut(), &cmds))
+        })
+        .collect::<Vec<(&str, Route)>>();
+    match output.as_deref() {
+        Some("csv") => print!("{}", routes_to_csv(&routes)),
+        Some("svg") => print!("{}", routes_to_svg(&routes)),
+        _ => {
+            for (name, route) in routes.iter() {
+                let stats = route.stats();
+                let end = route.points.last().unwrap().ship;
+                println!("{}: {}", name, end.manhattan());
+                println!(
+                    "  furthest: {} at step {}",
+                    stats.furthest, stats.furthest_step
+                );
+                println!(
+                    "  bounding box: ({}, {}) - ({}, {})",
+                    stats.min.x, stats.min.y, stats.max.x, stats.max.y
+                );
+                println!("  travelled: {:.3}", stats.travelled);
+                for (step, point) in route.checkpoints() {
+                    println!(
+                        "  checkpoint {} at step {}: ({}, {}), distance {}",
+                        point.checkpoint.as_ref().unwrap(),
+                        step,
+                        point.ship.x,
+                        point.ship.y,
+                        point.ship.manhattan()
+                    );
+                }
+            }
+        }
+    }
 }
@@ -95,15 +95,44 @@ impl Record {
     }
 }
 
-fn text_to_records(inp: &str) -> Vec<Record> {
+// Problem found while parsing passport batch text. Line numbers are 1-based.
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    // Same field appears twice in one record; the first value wins.
+    DuplicateField { line: usize, field: String },
+    // Item is not of the form "key:value" with a non-empty key and value.
+    MalformedField { line: usize, item: String },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::DuplicateField { line, field } => {
+                write!(f, "line {}: duplicate field {:?}", line, field)
+            }
+            ParseError::MalformedField { line, item } => {
+                write!(f, "line {}: malformed field {:?}", line, item)
+            }
+        }
+    }
+}
+
+// Parses passport batch text into records. Records are separated by blank lines (lines with only
+// whitespace count as blank, so CRLF input works too). Bad items are skipped and reported, the rest
+// of the record is kept.
+fn text_to_records(inp: &str) -> (Vec<Record>, Vec<ParseError>) {
     let mut records = vec![];
+    let mut errors = vec![];
     let mut record = Record {
         fields: HashMap::new(),
     };
-    for line in inp.lines() {
-        if line.len() == 0 {
-            records.push(record.clone());
-            record.fields.clear();
+    for (i, line) in inp.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            if !record.fields.is_empty() {
+                records.push(record.clone());
+                record.fields.clear();
+            }
             continue;
         }
         // line:
@@ -111,17 +140,266 @@ fn text_to_records(inp: &str) -> Vec<Record> {
         for item in line.split_whitespace() {
             // item:
             // hgt:177cm
-            let split_field = item.split(':').collect::<Vec<&str>>();
-            assert_eq!(split_field.len(), 2);
-            record
-                .fields
-                .insert(String::from(split_field[0]), String::from(split_field[1]));
+            let mut split_field = item.splitn(2, ':');
+            let key = split_field.next().unwrap_or("");
+            let value = split_field.next().unwrap_or("");
+            if key.is_empty() || value.is_empty() || value.contains(':') {
+                errors.push(ParseError::MalformedField {
+                    line: line_no,
+                    item: String::from(item),
+                });
+                continue;
+            }
+            if record.fields.contains_key(key) {
+                errors.push(ParseError::DuplicateField {
+                    line: line_no,
+                    field: String::from(key),
+                });
+                continue;
+            }
+            record.fields.insert(String::from(key), String::from(value));
         }
     }
-    if record.fields.len() > 0 {
+    if !record.fields.is_empty() {
         records.push(record);
     }
-    records
+    (records, errors)
+}
+
+// Returns the union of all field names used by records, sorted.
+fn all_field_names(records: &[Record]) -> Vec<String> {
+    let mut names = records
+        .iter()
+        .flat_map(|r| r.fields.keys().cloned())
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Writes records as JSON Lines, one object per record with keys in sorted order:
+// {"byr":"1920","ecl":"gry"}
+fn records_to_jsonl(records: &[Record]) -> String {
+    let mut out = String::new();
+    for record in records.iter() {
+        let mut keys = record.fields.keys().collect::<Vec<&String>>();
+        keys.sort();
+        let items = keys
+            .iter()
+            .map(|k| {
+                format!(
+                    "\"{}\":\"{}\"",
+                    json_escape(k),
+                    json_escape(&record.fields[*k])
+                )
+            })
+            .collect::<Vec<String>>();
+        out.push('{');
+        out.push_str(&items.join(","));
+        out.push_str("}\n");
+    }
+    out
+}
+
+// Parses a JSON string literal starting at chars[*pos] (which must be '"'), advancing pos past it.
+fn parse_json_string(chars: &[char], pos: &mut usize) -> Option<String> {
+    if chars.get(*pos) != Some(&'"') {
+        return None;
+    }
+    *pos += 1;
+    let mut out = String::new();
+    loop {
+        let c = *chars.get(*pos)?;
+        *pos += 1;
+        match c {
+            '"' => return Some(out),
+            '\\' => {
+                let e = *chars.get(*pos)?;
+                *pos += 1;
+                out.push(match e {
+                    '"' => '"',
+                    '\\' => '\\',
+                    '/' => '/',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex = chars.get(*pos..*pos + 4)?.iter().collect::<String>();
+                        *pos += 4;
+                        std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    _ => return None,
+                });
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+fn skip_json_whitespace(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+        *pos += 1;
+    }
+}
+
+// Parses one JSON Lines record: a flat object whose values are all strings.
+fn parse_json_record(line: &str) -> Option<Record> {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut pos = 0;
+    let mut fields = HashMap::new();
+    skip_json_whitespace(&chars, &mut pos);
+    if chars.get(pos) != Some(&'{') {
+        return None;
+    }
+    pos += 1;
+    skip_json_whitespace(&chars, &mut pos);
+    if chars.get(pos) == Some(&'}') {
+        pos += 1;
+    } else {
+        loop {
+            skip_json_whitespace(&chars, &mut pos);
+            let key = parse_json_string(&chars, &mut pos)?;
+            skip_json_whitespace(&chars, &mut pos);
+            if chars.get(pos) != Some(&':') {
+                return None;
+            }
+            pos += 1;
+            skip_json_whitespace(&chars, &mut pos);
+            let value = parse_json_string(&chars, &mut pos)?;
+            if fields.insert(key, value).is_some() {
+                return None;
+            }
+            skip_json_whitespace(&chars, &mut pos);
+            match chars.get(pos)? {
+                ',' => pos += 1,
+                '}' => {
+                    pos += 1;
+                    break;
+                }
+                _ => return None,
+            }
+        }
+    }
+    skip_json_whitespace(&chars, &mut pos);
+    if pos != chars.len() {
+        return None;
+    }
+    Some(Record { fields })
+}
+
+// Reads records written by records_to_jsonl(). Returns the 1-based line number of the first bad
+// line on failure.
+fn jsonl_to_records(inp: &str) -> Result<Vec<Record>, usize> {
+    inp.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_json_record(line).ok_or(i + 1))
+        .collect()
+}
+
+fn csv_quote(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+// Writes records as CSV. The header lists every field name used by any record; a field missing
+// from a record is an empty cell (field values are never empty, see text_to_records()).
+fn records_to_csv(records: &[Record]) -> String {
+    let names = all_field_names(records);
+    let mut out = names
+        .iter()
+        .map(|n| csv_quote(n))
+        .collect::<Vec<String>>()
+        .join(",");
+    out.push('\n');
+    for record in records.iter() {
+        let row = names
+            .iter()
+            .map(|n| record.fields.get(n).map_or(String::new(), |v| csv_quote(v)))
+            .collect::<Vec<String>>();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Splits one CSV line into cells, handling quoted cells.
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (false, ',') => cells.push(std::mem::take(&mut cell)),
+            (false, '"') if cell.is_empty() => quoted = true,
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => {
+                quoted = false;
+                if chars.peek().is_some_and(|&c| c != ',') {
+                    return None;
+                }
+            }
+            (_, c) => cell.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    cells.push(cell);
+    Some(cells)
+}
+
+// Reads records written by records_to_csv(). Returns the 1-based line number of the first bad
+// line on failure.
+fn csv_to_records(inp: &str) -> Result<Vec<Record>, usize> {
+    let mut lines = inp
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        Some((i, line)) => split_csv_line(line).ok_or(i + 1)?,
+        None => return Ok(vec![]),
+    };
+    let mut records = vec![];
+    for (i, line) in lines {
+        let cells = split_csv_line(line).ok_or(i + 1)?;
+        if cells.len() != header.len() {
+            return Err(i + 1);
+        }
+        let fields = header
+            .iter()
+            .cloned()
+            .zip(cells)
+            .filter(|(_, v)| !v.is_empty())
+            .collect();
+        records.push(Record { fields });
+    }
+    Ok(records)
 }
 
 fn count_valid_records(records: &Vec<Record>, required_fields: &Vec<&str>) -> u32 {
@@ -140,8 +418,80 @@ fn count_valid_records_advanced(records: &Vec<Record>) -> u32 {
     })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_to_records_reports_errors() {
+        let (records, errors) = text_to_records("byr:1920 iyr\r\nbyr:1921 ecl:gry\r\n\r\npid:1\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].fields["byr"], "1920");
+        assert_eq!(records[0].fields["ecl"], "gry");
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::MalformedField {
+                    line: 1,
+                    item: String::from("iyr")
+                },
+                ParseError::DuplicateField {
+                    line: 2,
+                    field: String::from("byr")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn jsonl_and_csv_round_trip() {
+        let (records, _) = text_to_records("a:1 b:\"x,y\"\n\nb:\\z\n");
+        let from_json = jsonl_to_records(&records_to_jsonl(&records)).unwrap();
+        let from_csv = csv_to_records(&records_to_csv(&records)).unwrap();
+        for (r1, r2) in from_json.iter().zip(from_csv.iter()) {
+            assert_eq!(r1.fields, r2.fields);
+        }
+        assert_eq!(from_json[0].fields, records[0].fields);
+        assert_eq!(from_json[1].fields, records[1].fields);
+    }
+}
+
+// Usage: day4 [FILE [FORMAT]]
+//
+// FILE defaults to input/4_1.txt. It is read as JSON Lines or CSV if its name ends in .jsonl or
+// .csv, and as passport batch text otherwise. If FORMAT ("jsonl" or "csv") is given, the records
+// are printed in that format instead of counting valid passports.
 fn main() {
-    let contents = std::fs::read_to_string("input/4_1.txt").expect("read failed");
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/4_1.txt"));
+    let format = std::env::args().nth(2);
+    let contents = std::fs::read_to_string(&path).expect("read failed");
+
+    let records = if path.ends_with(".jsonl") {
+        jsonl_to_records(&contents).unwrap_or_else(|line| panic!("bad JSON at line {}", line))
+    } else if path.ends_with(".csv") {
+        csv_to_records(&contents).unwrap_or_else(|line| panic!("bad CSV at line {}", line))
+    } else {
+        let (records, errors) = text_to_records(contents.as_str());
+        for error in errors.iter() {
+            eprintln!("{}", error);
+        }
+        records
+    };
+
+    match format.as_deref() {
+        Some("jsonl") => {
+            print!("{}", records_to_jsonl(&records));
+            return;
+        }
+        Some("csv") => {
+            print!("{}", records_to_csv(&records));
+            return;
+        }
+        Some(other) => panic!("unknown format {:?}", other),
+        None => {}
+    }
 
     // byr (Birth Year)
     // iyr (Issue Year)
@@ -153,7 +503,6 @@ fn main() {
     // cid (Country ID) - optional
     let required_fields = vec!["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"];
 
-    let records = text_to_records(contents.as_str());
     dbg!(records.len());
     // dbg!(&records[0], &records[1]);
 
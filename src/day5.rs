@@ -1,68 +1,332 @@
-use std::collections::HashSet;
+// Plane layout for boarding passes: how many bits encode the row and the column, and which
+// letters stand for the lower (0) and upper (1) half of each.
+#[derive(Debug, Clone)]
+struct Geometry {
+    row_bits: u32,
+    col_bits: u32,
+    // (lower, upper), e.g., ('F', 'B').
+    row_letters: (char, char),
+    // (lower, upper), e.g., ('L', 'R').
+    col_letters: (char, char),
+}
+
+impl Geometry {
+    // The puzzle's plane: 128 rows, 8 columns.
+    fn standard() -> Geometry {
+        Geometry {
+            row_bits: 7,
+            col_bits: 3,
+            row_letters: ('F', 'B'),
+            col_letters: ('L', 'R'),
+        }
+    }
+
+    fn seat_count(&self) -> u32 {
+        1 << (self.row_bits + self.col_bits)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CodecError {
+    // Geometry can't be used: too many bits, or letters that aren't distinct.
+    BadGeometry,
+    BadLength { expected: usize, got: usize },
+    // Unexpected letter at given (0-based) position.
+    BadLetter { pos: usize, letter: char },
+    // Seat id doesn't fit into the plane.
+    SeatOutOfRange(u32),
+    // Plane has more seats than a SeatMap keeps, see SeatMap::MAX_SEATS.
+    PlaneTooLarge(u32),
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CodecError::BadGeometry => write!(f, "bad plane geometry"),
+            CodecError::BadLength { expected, got } => {
+                write!(f, "expected {} letters, got {}", expected, got)
+            }
+            CodecError::BadLetter { pos, letter } => {
+                write!(f, "unexpected letter {:?} at position {}", letter, pos)
+            }
+            CodecError::SeatOutOfRange(id) => write!(f, "seat id {} is outside the plane", id),
+            CodecError::PlaneTooLarge(seats) => {
+                write!(f, "plane has {} seats, too many to map", seats)
+            }
+        }
+    }
+}
 
-// Parses seat representation into seat id.
+// Converts between boarding pass strings and seat ids.
 //
 // BFBFBBBRLL
 // BFBFBBB RLL
 // 1010111 100
 // ^ row   ^ column
 //   0..127   0..7
-fn str_to_seat_id(s: &str) -> u32 {
-    let (row_str, col_str) = s.split_at(7);
-    let mut row = 0;
-    for c in row_str.chars() {
-        row = 2 * row
-            + match c {
-                'B' => 1,
-                'F' => 0,
-                _ => {
-                    panic!("bad news");
-                }
+//
+// seat id = row * 8 + column
+#[derive(Debug, Clone)]
+struct SeatCodec {
+    geometry: Geometry,
+}
+
+impl SeatCodec {
+    fn new(geometry: Geometry) -> Result<SeatCodec, CodecError> {
+        let (r0, r1) = geometry.row_letters;
+        let (c0, c1) = geometry.col_letters;
+        if geometry.row_bits + geometry.col_bits > 31 || r0 == r1 || c0 == c1 {
+            return Err(CodecError::BadGeometry);
+        }
+        Ok(SeatCodec { geometry })
+    }
+
+    fn pass_len(&self) -> usize {
+        (self.geometry.row_bits + self.geometry.col_bits) as usize
+    }
+
+    // Parses a boarding pass into a seat id.
+    fn decode(&self, s: &str) -> Result<u32, CodecError> {
+        let letters = s.chars().collect::<Vec<char>>();
+        if letters.len() != self.pass_len() {
+            return Err(CodecError::BadLength {
+                expected: self.pass_len(),
+                got: letters.len(),
+            });
+        }
+        let mut id = 0;
+        for (pos, &letter) in letters.iter().enumerate() {
+            let (zero, one) = if pos < self.geometry.row_bits as usize {
+                self.geometry.row_letters
+            } else {
+                self.geometry.col_letters
+            };
+            let bit = match letter {
+                l if l == zero => 0,
+                l if l == one => 1,
+                _ => return Err(CodecError::BadLetter { pos, letter }),
             };
+            id = 2 * id + bit;
+        }
+        Ok(id)
     }
-    let mut col = 0;
-    for c in col_str.chars() {
-        col = 2 * col
-            + match c {
-                'R' => 1,
-                'L' => 0,
-                _ => {
-                    panic!("bad news");
+
+    // Turns a seat id back into a boarding pass.
+    fn encode(&self, id: u32) -> Result<String, CodecError> {
+        if id >= self.geometry.seat_count() {
+            return Err(CodecError::SeatOutOfRange(id));
+        }
+        let len = self.pass_len();
+        Ok((0..len)
+            .map(|pos| {
+                let (zero, one) = if pos < self.geometry.row_bits as usize {
+                    self.geometry.row_letters
+                } else {
+                    self.geometry.col_letters
+                };
+                if (id >> (len - 1 - pos)) & 1 == 1 {
+                    one
+                } else {
+                    zero
                 }
-            };
+            })
+            .collect())
     }
-    row * 8 + col
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn standard_codec() -> SeatCodec {
+        SeatCodec::new(Geometry::standard()).unwrap()
+    }
+
     #[test]
-    fn str_to_seat_id_works() {
-        assert_eq!(str_to_seat_id("BFFFBBFRRR"), 567);
+    fn decode_works() {
+        assert_eq!(standard_codec().decode("BFFFBBFRRR"), Ok(567));
+    }
+
+    #[test]
+    fn encode_works() {
+        assert_eq!(standard_codec().encode(567), Ok(String::from("BFFFBBFRRR")));
+        assert_eq!(
+            standard_codec().encode(1024),
+            Err(CodecError::SeatOutOfRange(1024))
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let codec = standard_codec();
+        assert_eq!(
+            codec.decode("BFFF"),
+            Err(CodecError::BadLength {
+                expected: 10,
+                got: 4
+            })
+        );
+        assert_eq!(
+            codec.decode("BFFFBBFRRB"),
+            Err(CodecError::BadLetter {
+                pos: 9,
+                letter: 'B'
+            })
+        );
+    }
+
+    #[test]
+    fn custom_geometry_round_trips() {
+        let codec = SeatCodec::new(Geometry {
+            row_bits: 5,
+            col_bits: 2,
+            row_letters: ('0', '1'),
+            col_letters: ('a', 'b'),
+        })
+        .unwrap();
+        for id in 0..128 {
+            assert_eq!(codec.decode(&codec.encode(id).unwrap()), Ok(id));
+        }
+        assert_eq!(codec.decode("01101ba"), Ok(0b0110110));
+    }
+
+    #[test]
+    fn seat_map_works() {
+        let codec = SeatCodec::new(Geometry {
+            row_bits: 2,
+            col_bits: 2,
+            row_letters: ('F', 'B'),
+            col_letters: ('L', 'R'),
+        })
+        .unwrap();
+        let map = SeatMap::build(&codec, "FFRL\nFBLL\nFBRL\nFFRL\nBBRRR\nFFRR\nBFLR\n").unwrap();
+        assert_eq!(map.render(), "0 ..##\n1 #.#.\n2 .#..\n3 ....\n");
+        assert_eq!(map.free_seats_between_taken(), vec![5]);
+        assert_eq!(map.duplicates, vec![(4, 2)]);
+        assert_eq!(map.invalid.len(), 1);
+        assert_eq!(map.invalid[0].0, 5);
+        assert_eq!(map.max_seat_id(), Some(9));
+
+        let codec = SeatCodec::new(Geometry {
+            row_bits: 28,
+            ..Geometry::standard()
+        })
+        .unwrap();
+        let error = SeatMap::build(&codec, "").unwrap_err();
+        assert_eq!(error, CodecError::PlaneTooLarge(1 << 31));
     }
 }
 
-// Finds a missing seat id N, where seats N - 1 and N + 1 do exist.
-fn find_my_seat(seats: &HashSet<u32>) -> u32 {
-    // N exists, N + 1 does not exist, N + 2 exists.
-    //           ^^^^^
-    for id in seats.iter() {
-        if !seats.contains(&(id + 1)) && seats.contains(&(id + 2)) {
-            return id + 1;
+// Occupancy of the whole plane, built from a list of boarding passes.
+#[derive(Debug)]
+struct SeatMap {
+    codec: SeatCodec,
+    // Indexed by seat id.
+    occupied: Vec<bool>,
+    // (line number, seat id) of passes for a seat that was already taken.
+    duplicates: Vec<(usize, u32)>,
+    // (line number, error) of passes that don't fit the plane's geometry.
+    invalid: Vec<(usize, CodecError)>,
+}
+
+impl SeatMap {
+    // Every seat takes a byte and a character of render(), so bigger planes are refused.
+    const MAX_SEATS: u32 = 1 << 20;
+
+    // Decodes one boarding pass per line. Line numbers are 1-based.
+    fn build(codec: &SeatCodec, passes: &str) -> Result<SeatMap, CodecError> {
+        let seats = codec.geometry.seat_count();
+        if seats > SeatMap::MAX_SEATS {
+            return Err(CodecError::PlaneTooLarge(seats));
+        }
+        let mut map = SeatMap {
+            codec: codec.clone(),
+            occupied: vec![false; seats as usize],
+            duplicates: vec![],
+            invalid: vec![],
+        };
+        for (i, line) in passes.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match codec.decode(line.trim()) {
+                Ok(id) if map.occupied[id as usize] => map.duplicates.push((i + 1, id)),
+                Ok(id) => map.occupied[id as usize] = true,
+                Err(e) => map.invalid.push((i + 1, e)),
+            }
         }
+        Ok(map)
+    }
+
+    fn max_seat_id(&self) -> Option<u32> {
+        self.occupied.iter().rposition(|&o| o).map(|id| id as u32)
+    }
+
+    // Finds every free seat id N where seats N - 1 and N + 1 are both taken.
+    fn free_seats_between_taken(&self) -> Vec<u32> {
+        (1..self.occupied.len().saturating_sub(1))
+            .filter(|&id| !self.occupied[id] && self.occupied[id - 1] && self.occupied[id + 1])
+            .map(|id| id as u32)
+            .collect()
+    }
+
+    // Draws the plane, one row per line, '#' for occupied and '.' for free seats:
+    //
+    //   0 ........
+    //   1 ...#.##.
+    fn render(&self) -> String {
+        let cols = 1 << self.codec.geometry.col_bits;
+        let rows = 1 << self.codec.geometry.row_bits;
+        let width = format!("{}", rows - 1).len();
+        let mut out = String::new();
+        for (row, seats) in self.occupied.chunks(cols).enumerate() {
+            let line = seats
+                .iter()
+                .map(|&o| if o { '#' } else { '.' })
+                .collect::<String>();
+            out.push_str(&format!("{:>width$} {}\n", row, line, width = width));
+        }
+        out
     }
-    panic!("sad panda");
 }
 
+// Usage: day5 [FILE [ROW_BITS COL_BITS [LETTERS]]]
+//
+// LETTERS gives the lower and upper row letters followed by the lower and upper column letters,
+// "FBLR" by default.
 fn main() {
-    let contents = std::fs::read_to_string("input/5.txt").expect("read failed");
-    let seat_ids = contents
-        .lines()
-        .map(|line| str_to_seat_id(line))
-        .collect::<HashSet<u32>>();
-    let max_seat_id = seat_ids.iter().max();
-    dbg!(max_seat_id);
-    dbg!(find_my_seat(&seat_ids));
+    let args = std::env::args().collect::<Vec<String>>();
+    let path = args.get(1).map_or("input/5.txt", |s| s.as_str());
+    let mut geometry = Geometry::standard();
+    if args.len() > 3 {
+        geometry.row_bits = args[2].parse().expect("bad ROW_BITS");
+        geometry.col_bits = args[3].parse().expect("bad COL_BITS");
+    }
+    if let Some(letters) = args.get(4) {
+        let letters = letters.chars().collect::<Vec<char>>();
+        assert_eq!(letters.len(), 4, "LETTERS must have 4 letters");
+        geometry.row_letters = (letters[0], letters[1]);
+        geometry.col_letters = (letters[2], letters[3]);
+    }
+    let codec = SeatCodec::new(geometry).expect("bad geometry");
+
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let seat_map = match SeatMap::build(&codec, &contents) {
+        Ok(seat_map) => seat_map,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    print!("{}", seat_map.render());
+    for (line, e) in seat_map.invalid.iter() {
+        eprintln!("line {}: {}", line, e);
+    }
+    for (line, id) in seat_map.duplicates.iter() {
+        eprintln!("line {}: duplicate pass for seat {}", line, id);
+    }
+    dbg!(seat_map.max_seat_id());
+    for id in seat_map.free_seats_between_taken() {
+        println!("free seat {} ({})", id, codec.encode(id).unwrap());
+    }
 }
@@ -1,42 +1,370 @@
-use std::collections::HashSet;
+// Letters that questions are named by. Each letter gets one bit in Answers, so there can be at
+// most 64 of them.
+#[derive(Debug, Clone)]
+struct Alphabet {
+    letters: Vec<char>,
+}
+
+impl Alphabet {
+    fn new(letters: &str) -> Alphabet {
+        let letters = letters.chars().collect::<Vec<char>>();
+        assert!(letters.len() <= 64, "alphabet too long");
+        Alphabet { letters }
+    }
+
+    // Questions a..z, as in the puzzle.
+    fn standard() -> Alphabet {
+        Alphabet::new("abcdefghijklmnopqrstuvwxyz")
+    }
 
-fn line_to_set(line: &str) -> HashSet<char> {
-    line.chars().collect()
+    fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    fn index_of(&self, c: char) -> Option<usize> {
+        self.letters.iter().position(|&l| l == c)
+    }
+
+    // Turns one line (one person's answers) into a set.
+    fn parse_answers(&self, line: &str) -> Result<Answers, char> {
+        line.chars().try_fold(Answers::empty(), |set, c| {
+            let i = self.index_of(c).ok_or(c)?;
+            Ok(set.union(Answers(1 << i)))
+        })
+    }
+
+    // Lists letters of questions in the set, in alphabet order.
+    fn letters_of(&self, answers: Answers) -> String {
+        (0..self.len())
+            .filter(|&i| answers.contains(i))
+            .map(|i| self.letters[i])
+            .collect()
+    }
 }
 
-// Find a union of all characters in given lines.
-fn questions_union(s: &str) -> HashSet<char> {
-    s.lines().fold(HashSet::new(), |set, line| {
-        set.union(&line_to_set(line)).cloned().collect()
-    })
+// Set of questions, one bit per question index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Answers(u64);
+
+impl Answers {
+    fn empty() -> Answers {
+        Answers(0)
+    }
+
+    fn contains(self, question: usize) -> bool {
+        (self.0 >> question) & 1 == 1
+    }
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn union(self, other: Answers) -> Answers {
+        Answers(self.0 | other.0)
+    }
+
+    fn intersection(self, other: Answers) -> Answers {
+        Answers(self.0 & other.0)
+    }
+
+    fn symmetric_difference(self, other: Answers) -> Answers {
+        Answers(self.0 ^ other.0)
+    }
 }
 
-// Find an intersection of all characters in given lines.
-fn questions_intersection(s: &str) -> HashSet<char> {
-    let mut lines = s.lines();
+// Answers of everyone in one group, one entry per person.
+#[derive(Debug, Clone)]
+struct Group {
+    members: Vec<Answers>,
+}
+
+impl Group {
+    // Parses a group, one person per line. Returns the first letter that's not in the alphabet on
+    // failure.
+    fn parse(alphabet: &Alphabet, s: &str) -> Result<Group, char> {
+        let members = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| alphabet.parse_answers(line.trim()))
+            .collect::<Result<Vec<Answers>, char>>()?;
+        Ok(Group { members })
+    }
+
+    // Questions anyone answered.
+    fn union(&self) -> Answers {
+        self.members
+            .iter()
+            .fold(Answers::empty(), |set, &m| set.union(m))
+    }
+
+    // Questions everyone answered. Empty for an empty group.
+    fn intersection(&self) -> Answers {
+        match self.members.split_first() {
+            Some((&first, rest)) => rest.iter().fold(first, |set, &m| set.intersection(m)),
+            None => Answers::empty(),
+        }
+    }
+
+    // Questions answered by an odd number of people.
+    fn symmetric_difference(&self) -> Answers {
+        self.members
+            .iter()
+            .fold(Answers::empty(), |set, &m| set.symmetric_difference(m))
+    }
 
-    let mut union = HashSet::new();
-    union.extend(line_to_set(lines.next().unwrap()));
-    lines.fold(union, |set, line| {
-        set.intersection(&line_to_set(line)).cloned().collect()
-    })
+    // Number of people who answered each question, indexed by question.
+    fn frequencies(&self, alphabet: &Alphabet) -> Vec<usize> {
+        (0..alphabet.len())
+            .map(|i| self.members.iter().filter(|m| m.contains(i)).count())
+            .collect()
+    }
+
+    // Questions answered by at least k people. With k = 0 that's every question.
+    fn answered_by_at_least(&self, alphabet: &Alphabet, k: usize) -> Answers {
+        self.frequencies(alphabet)
+            .iter()
+            .enumerate()
+            .filter(|(_, &cnt)| cnt >= k)
+            .fold(Answers::empty(), |set, (i, _)| set.union(Answers(1 << i)))
+    }
+}
+
+// Statistics over all groups in a survey.
+#[derive(Debug)]
+struct Report {
+    alphabet: Alphabet,
+    group_sizes: Vec<usize>,
+    // Number of people who answered each question, indexed by question.
+    people_per_question: Vec<usize>,
+    // Number of groups where anyone answered each question, indexed by question.
+    groups_per_question: Vec<usize>,
+    // Indices of groups where everyone answered exactly the same questions.
+    unanimous_groups: Vec<usize>,
+}
+
+impl Report {
+    fn build(alphabet: &Alphabet, groups: &[Group]) -> Report {
+        let mut people_per_question = vec![0; alphabet.len()];
+        let mut groups_per_question = vec![0; alphabet.len()];
+        for g in groups.iter() {
+            let union = g.union();
+            for (i, cnt) in g.frequencies(alphabet).iter().enumerate() {
+                people_per_question[i] += cnt;
+                if union.contains(i) {
+                    groups_per_question[i] += 1;
+                }
+            }
+        }
+        Report {
+            alphabet: alphabet.clone(),
+            group_sizes: groups.iter().map(|g| g.members.len()).collect(),
+            people_per_question,
+            groups_per_question,
+            unanimous_groups: groups
+                .iter()
+                .enumerate()
+                .filter(|(_, g)| !g.members.is_empty() && g.union() == g.intersection())
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    fn people(&self) -> usize {
+        self.group_sizes.iter().sum()
+    }
+
+    // Fraction of all people who answered given question.
+    fn people_rate(&self, question: usize) -> f64 {
+        self.people_per_question[question] as f64 / self.people().max(1) as f64
+    }
+
+    // Fraction of groups where anyone answered given question.
+    fn group_rate(&self, question: usize) -> f64 {
+        self.groups_per_question[question] as f64 / self.group_sizes.len().max(1) as f64
+    }
+
+    // Questions answered by the most (or, with most = false, the fewest) people. Ties are all
+    // included.
+    fn extreme_questions(&self, most: bool) -> Answers {
+        let counts = &self.people_per_question;
+        let target = if most {
+            counts.iter().max()
+        } else {
+            counts.iter().min()
+        };
+        counts
+            .iter()
+            .enumerate()
+            .filter(|(_, cnt)| Some(*cnt) == target)
+            .fold(Answers::empty(), |set, (i, _)| set.union(Answers(1 << i)))
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "{} groups, {} people\n",
+            self.group_sizes.len(),
+            self.people()
+        ));
+        for (i, size) in self.group_sizes.iter().enumerate() {
+            out.push_str(&format!("group {}: {} people\n", i, size));
+        }
+        for (i, letter) in self.alphabet.letters.iter().enumerate() {
+            out.push_str(&format!(
+                "question {}: {:.1}% of people, {:.1}% of groups\n",
+                letter,
+                100.0 * self.people_rate(i),
+                100.0 * self.group_rate(i)
+            ));
+        }
+        out.push_str(&format!(
+            "most common: {}\n",
+            self.alphabet.letters_of(self.extreme_questions(true))
+        ));
+        out.push_str(&format!(
+            "least common: {}\n",
+            self.alphabet.letters_of(self.extreme_questions(false))
+        ));
+        out.push_str(&format!("unanimous groups: {:?}\n", self.unanimous_groups));
+        out
+    }
+
+    // One row per group: index, size and whether everyone gave the same answers.
+    fn groups_csv(&self) -> String {
+        let mut out = String::from("group,size,unanimous\n");
+        for (i, size) in self.group_sizes.iter().enumerate() {
+            let unanimous = self.unanimous_groups.contains(&i);
+            out.push_str(&format!("{},{},{}\n", i, size, unanimous));
+        }
+        out
+    }
+
+    // One row per question: how many people and groups answered it and the matching rates.
+    fn questions_csv(&self) -> String {
+        let mut out = String::from("question,people,people_rate,groups,group_rate\n");
+        for (i, letter) in self.alphabet.letters.iter().enumerate() {
+            out.push_str(&format!(
+                "{},{},{:.4},{},{:.4}\n",
+                letter,
+                self.people_per_question[i],
+                self.people_rate(i),
+                self.groups_per_question[i],
+                self.group_rate(i)
+            ));
+        }
+        out
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn group(s: &str) -> Group {
+        Group::parse(&Alphabet::standard(), s).unwrap()
+    }
+
     #[test]
     fn questions_intersection_works() {
-        assert_eq!(questions_intersection("ab\nac"), line_to_set("a"));
+        let alphabet = Alphabet::standard();
+        assert_eq!(
+            group("ab\nac").intersection(),
+            alphabet.parse_answers("a").unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_group_works() {
+        assert_eq!(group("").intersection(), Answers::empty());
+        assert_eq!(group("").union(), Answers::empty());
+    }
+
+    #[test]
+    fn group_algebra_works() {
+        let alphabet = Alphabet::standard();
+        let g = group("abc\nbcd\ncx");
+        assert_eq!(alphabet.letters_of(g.union()), "abcdx");
+        assert_eq!(alphabet.letters_of(g.intersection()), "c");
+        assert_eq!(alphabet.letters_of(g.symmetric_difference()), "acdx");
+        assert_eq!(
+            alphabet.letters_of(g.answered_by_at_least(&alphabet, 2)),
+            "bc"
+        );
+        assert_eq!(&g.frequencies(&alphabet)[0..4], &[1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn report_works() {
+        let alphabet = Alphabet::new("abc");
+        let groups = vec![group("ab\nba"), group("a\nb"), group("c")];
+        let report = Report::build(&alphabet, &groups);
+        assert_eq!(report.people_per_question, vec![3, 3, 1]);
+        assert_eq!(report.groups_per_question, vec![2, 2, 1]);
+        assert_eq!(report.unanimous_groups, vec![0, 2]);
+        assert_eq!(alphabet.letters_of(report.extreme_questions(true)), "ab");
+        assert_eq!(alphabet.letters_of(report.extreme_questions(false)), "c");
+        assert_eq!(
+            report.groups_csv(),
+            "group,size,unanimous\n0,2,true\n1,2,false\n2,1,true\n"
+        );
+    }
+
+    #[test]
+    fn unknown_letter_is_error() {
+        assert_eq!(
+            Group::parse(&Alphabet::new("ab"), "ab\nac").unwrap_err(),
+            'c'
+        );
     }
 }
 
+// Usage: day6 [FILE [MODE]]
+//
+// MODE is one of:
+// * K (a number): also sum up questions that at least K people in a group answered.
+// * "report": print survey statistics.
+// * "groups.csv" or "questions.csv": print survey statistics as CSV.
 fn main() {
-    let contents = std::fs::read_to_string("input/6.txt").expect("read failed");
-    let groups = contents.split("\n\n").collect::<Vec<&str>>();
-    let union_sum: usize = groups.iter().map(|x| questions_union(x).len()).sum();
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/6.txt"));
+    let mode = std::env::args().nth(2);
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let alphabet = Alphabet::standard();
+    let groups = contents
+        .split("\n\n")
+        .map(|s| Group::parse(&alphabet, s).expect("unknown question"))
+        .collect::<Vec<Group>>();
+
+    match mode.as_deref() {
+        Some("report") => {
+            print!("{}", Report::build(&alphabet, &groups).to_text());
+            return;
+        }
+        Some("groups.csv") => {
+            print!("{}", Report::build(&alphabet, &groups).groups_csv());
+            return;
+        }
+        Some("questions.csv") => {
+            print!("{}", Report::build(&alphabet, &groups).questions_csv());
+            return;
+        }
+        _ => {}
+    }
+
+    let union_sum: usize = groups.iter().map(|g| g.union().len()).sum();
     dbg!(union_sum);
-    let intersection_sum: usize = groups.iter().map(|x| questions_intersection(x).len()).sum();
+    let intersection_sum: usize = groups.iter().map(|g| g.intersection().len()).sum();
     dbg!(intersection_sum);
+    let symmetric_difference_sum: usize =
+        groups.iter().map(|g| g.symmetric_difference().len()).sum();
+    dbg!(symmetric_difference_sum);
+    if let Some(k) = mode {
+        let k = k.parse::<usize>().expect("bad MODE");
+        let quorum_sum: usize = groups
+            .iter()
+            .map(|g| g.answered_by_at_least(&alphabet, k).len())
+            .sum();
+        dbg!(k, quorum_sum);
+    }
 }
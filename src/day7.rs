@@ -1,10 +1,5 @@
-#[macro_use]
-extern crate lazy_static;
-
 use std::collections::{HashMap, HashSet};
 
-use regex::Regex;
-
 #[derive(Debug)]
 struct BagAndCount {
     name: String,
@@ -21,6 +16,32 @@ struct Rules {
     rules: HashMap<String, Contents>,
 }
 
+// Part of a rules file that couldn't be parsed. Line and column are 1-based, column counts
+// characters.
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    fragment: String,
+    reason: &'static str,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "line {} column {}: {}: {:?}",
+            self.line, self.column, self.reason, self.fragment
+        )
+    }
+}
+
+// Checks that a colour name is one or more words made of word characters (like regex's \w).
+fn is_colour(s: &str) -> bool {
+    s.split(' ')
+        .all(|w| !w.is_empty() && w.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
 impl Contents {
     // Parses one line from rules.
     //
@@ -28,110 +49,794 @@ impl Contents {
     // faded yellow bags contain 4 mirrored fuchsia bags, 4 dotted indigo bags, 3 faded orange bags, 5 plaid crimson bags.
     // Into this:
     // ("faded yellow", Contents{BagAndCount{name="mirrored fuchsia", count=4} ...})
-    fn parse(line: &str) -> (String, Contents) {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^(\w+ \w+) bags contain (.*)\.$").unwrap();
-            static ref RE2: Regex = Regex::new(r"(\d+) (\w+ \w+) bags?").unwrap();
+    //
+    // Colour names can have any number of words. A bag can also contain "no other bags". Returns
+    // every bad fragment of the line (with line number 0, see Rules::parse()) on failure.
+    fn parse(line: &str) -> Result<(String, Contents), Vec<ParseError>> {
+        let error = |start: usize, fragment: &str, reason| ParseError {
+            line: 0,
+            column: line[..start].chars().count() + 1,
+            fragment: String::from(fragment),
+            reason,
+        };
+
+        let line = line.trim_end();
+        const CONTAIN: &str = " bags contain ";
+        let split = match line.find(CONTAIN) {
+            Some(split) => split,
+            None => {
+                return Err(vec![error(0, line, "expected \"<colour> bags contain\"")]);
+            }
+        };
+        let mut errors = vec![];
+        let name = &line[..split];
+        if !is_colour(name) {
+            errors.push(error(0, name, "bad colour"));
         }
-        let captures = RE.captures(line).expect("failed to parse line");
-        let name = String::from(&captures[1]);
-        // let contents_str = String::from(&captures[2]);
+
+        let list_start = split + CONTAIN.len();
+        let list = match line.strip_suffix('.') {
+            Some(stripped) => &stripped[list_start.min(stripped.len())..],
+            None => {
+                errors.push(error(line.len(), "", "missing \".\" at the end"));
+                &line[list_start..]
+            }
+        };
 
         let mut bags = vec![];
-        for capture in RE2.captures_iter(&captures[2]) {
-            let count = capture[1].parse::<u32>().unwrap();
-            let name = String::from(&capture[2]);
-            bags.push(BagAndCount { name, count });
+        if list != "no other bags" {
+            let mut start = list_start;
+            for item in list.split(", ") {
+                // item:
+                // 4 mirrored fuchsia bags
+                let words = item.split(' ').collect::<Vec<&str>>();
+                let count = words[0].parse::<u32>().ok().filter(|&c| c > 0);
+                let colour = words
+                    .get(1..words.len().saturating_sub(1))
+                    .map(|w| w.join(" "))
+                    .unwrap_or_default();
+                let suffix = words.last().unwrap();
+                match count {
+                    _ if item.is_empty() => errors.push(error(start, item, "empty item")),
+                    None => errors.push(error(start, item, "expected a positive count")),
+                    Some(_) if !is_colour(&colour) || colour.is_empty() => {
+                        errors.push(error(start, item, "bad colour"))
+                    }
+                    Some(_) if *suffix != "bag" && *suffix != "bags" => {
+                        errors.push(error(start, item, "expected \"bag\" or \"bags\""))
+                    }
+                    Some(count) => bags.push(BagAndCount {
+                        name: colour,
+                        count,
+                    }),
+                }
+                start += item.len() + 2;
+            }
         }
 
-        (name, Contents { bags })
+        if errors.is_empty() {
+            Ok((String::from(name), Contents { bags }))
+        } else {
+            Err(errors)
+        }
     }
 }
 
 impl Rules {
-    // Parses the whole rules file.
-    fn parse(lines: &str) -> Rules {
+    // Parses the whole rules file. Lines that can't be parsed are skipped and all their problems
+    // are returned. Blank lines are ignored.
+    fn parse(lines: &str) -> (Rules, Vec<ParseError>) {
         let mut rules = Rules {
             rules: HashMap::new(),
         };
-        for line in lines.lines() {
-            let (name, contents) = Contents::parse(line);
-            rules.rules.insert(name, contents);
+        let mut errors = vec![];
+        for (i, line) in lines.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Contents::parse(line) {
+                Ok((name, _)) if rules.rules.contains_key(&name) => errors.push(ParseError {
+                    line: i + 1,
+                    column: 1,
+                    fragment: name,
+                    reason: "second rule for the same colour",
+                }),
+                Ok((name, contents)) => {
+                    rules.rules.insert(name, contents);
+                }
+                Err(line_errors) => errors.extend(
+                    line_errors
+                        .into_iter()
+                        .map(|e| ParseError { line: i + 1, ..e }),
+                ),
+            }
         }
-        rules
+        (rules, errors)
+    }
+}
+
+// Total number of bags. Counts grow exponentially with nesting depth, so this is wide, and all
+// arithmetic on it is checked.
+type BagCount = u128;
+
+// Directed graph of bags. An edge A -> B with count n means that A directly contains n B bags.
+// Every bag that's mentioned anywhere in the rules is a node, nodes are sorted by name.
+#[derive(Debug)]
+struct BagGraph {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    // Indexed by node: (child node, count).
+    children: Vec<Vec<(usize, u32)>>,
+    // Indexed by node: nodes that directly contain it.
+    parents: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum GraphError {
+    // Bags along a cycle, starting and ending with the same bag.
+    Cycle(Vec<String>),
+    UnknownBag(String),
+    // Bag count doesn't fit into an integer.
+    Overflow(String),
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GraphError::Cycle(bags) => write!(f, "bags contain themselves: {}", bags.join(" -> ")),
+            GraphError::UnknownBag(bag) => write!(f, "unknown bag {:?}", bag),
+            GraphError::Overflow(bag) => write!(f, "too many bags inside {:?}", bag),
+        }
+    }
+}
+
+impl BagGraph {
+    fn from_rules(rules: &Rules) -> BagGraph {
+        let mut names = rules
+            .rules
+            .iter()
+            .flat_map(|(name, contents)| {
+                std::iter::once(name.clone()).chain(contents.bags.iter().map(|b| b.name.clone()))
+            })
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        let index = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect::<HashMap<String, usize>>();
+
+        let mut children = vec![vec![]; names.len()];
+        let mut parents = vec![vec![]; names.len()];
+        for (name, contents) in rules.rules.iter() {
+            let parent = index[name];
+            for b in contents.bags.iter() {
+                let child = index[&b.name];
+                children[parent].push((child, b.count));
+                parents[child].push(parent);
+            }
+        }
+        for c in children.iter_mut() {
+            c.sort_unstable();
+        }
+        for p in parents.iter_mut() {
+            p.sort_unstable();
+            p.dedup();
+        }
+
+        BagGraph {
+            names,
+            index,
+            children,
+            parents,
+        }
+    }
+
+    fn node(&self, bag: &str) -> Result<usize, GraphError> {
+        self.index
+            .get(bag)
+            .cloned()
+            .ok_or_else(|| GraphError::UnknownBag(String::from(bag)))
+    }
+
+    // Finds some cycle, if there is one. Iterative DFS, so deep graphs don't overflow the stack.
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        // 0: not visited yet, 1: on the current DFS path, 2: done.
+        let mut state = vec![0u8; self.names.len()];
+        for start in 0..self.names.len() {
+            if state[start] != 0 {
+                continue;
+            }
+            // (node, index of the next child to look at)
+            let mut path = vec![(start, 0)];
+            state[start] = 1;
+            while let Some(&mut (node, ref mut next)) = path.last_mut() {
+                if let Some(&(child, _)) = self.children[node].get(*next) {
+                    *next += 1;
+                    match state[child] {
+                        0 => {
+                            state[child] = 1;
+                            path.push((child, 0));
+                        }
+                        1 => {
+                            let from = path.iter().position(|&(n, _)| n == child).unwrap();
+                            let mut cycle = path[from..]
+                                .iter()
+                                .map(|&(n, _)| self.names[n].clone())
+                                .collect::<Vec<String>>();
+                            cycle.push(self.names[child].clone());
+                            return Some(cycle);
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[node] = 2;
+                    path.pop();
+                }
+            }
+        }
+        None
+    }
+
+    // Orders nodes so that every bag comes before all bags it contains.
+    fn topological_order(&self) -> Result<Vec<usize>, GraphError> {
+        let mut in_degree = self.parents.iter().map(|p| p.len()).collect::<Vec<usize>>();
+        let mut ready = (0..self.names.len())
+            .filter(|&n| in_degree[n] == 0)
+            .collect::<Vec<usize>>();
+        ready.reverse();
+        let mut order = vec![];
+        while let Some(node) = ready.pop() {
+            order.push(node);
+            let mut seen = HashSet::new();
+            for &(child, _) in self.children[node].iter() {
+                // Parents are deduplicated, so only count each child once.
+                if seen.insert(child) {
+                    in_degree[child] -= 1;
+                    if in_degree[child] == 0 {
+                        ready.push(child);
+                    }
+                }
+            }
+        }
+        if order.len() != self.names.len() {
+            return Err(GraphError::Cycle(self.find_cycle().unwrap()));
+        }
+        Ok(order)
+    }
+
+    // Finds all bags that can (directly or indirectly) contain given bag.
+    fn outer_bags(&self, bag: &str) -> Result<HashSet<String>, GraphError> {
+        Ok(self
+            .outer_nodes(self.node(bag)?)
+            .into_iter()
+            .map(|n| self.names[n].clone())
+            .collect())
+    }
+
+    // Finds all nodes that can (directly or indirectly) contain given node.
+    fn outer_nodes(&self, node: usize) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut queue = vec![node];
+        while let Some(node) = queue.pop() {
+            for &p in self.parents[node].iter() {
+                if seen.insert(p) {
+                    queue.push(p);
+                }
+            }
+        }
+        seen
+    }
+
+    // Counts how many bags of each colour are (directly or indirectly) inside given bag. Only
+    // colours that appear at least once are listed, sorted by name.
+    fn inner_bag_breakdown(&self, bag: &str) -> Result<Vec<(String, BagCount)>, GraphError> {
+        let root = self.node(bag)?;
+        // How many bags of each colour there are, filled in from the root inwards.
+        let mut copies = vec![0 as BagCount; self.names.len()];
+        copies[root] = 1;
+        for &node in self.topological_order()?.iter() {
+            if copies[node] == 0 {
+                continue;
+            }
+            for &(child, count) in self.children[node].iter() {
+                copies[child] = copies[node]
+                    .checked_mul(count as BagCount)
+                    .and_then(|c| c.checked_add(copies[child]))
+                    .ok_or_else(|| GraphError::Overflow(String::from(bag)))?;
+            }
+        }
+        Ok((0..self.names.len())
+            .filter(|&n| n != root && copies[n] > 0)
+            .map(|n| (self.names[n].clone(), copies[n]))
+            .collect())
+    }
+
+    // Counts how many bags are (directly or indirectly) inside given bag.
+    fn inner_bag_count(&self, bag: &str) -> Result<BagCount, GraphError> {
+        self.inner_bag_breakdown(bag)?
+            .iter()
+            .try_fold(0 as BagCount, |total, (_, c)| total.checked_add(*c))
+            .ok_or_else(|| GraphError::Overflow(String::from(bag)))
+    }
+
+    // Exports the graph in Graphviz DOT format, with edges labelled by bag counts.
+    fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph bags {\n");
+        for (node, name) in self.names.iter().enumerate() {
+            out.push_str(&format!("    {};\n", quote(name)));
+            for &(child, count) in self.children[node].iter() {
+                out.push_str(&format!(
+                    "    {} -> {} [label=\"{}\"];\n",
+                    quote(name),
+                    quote(&self.names[child]),
+                    count
+                ));
+            }
+        }
+        out.push_str("}\n");
+        out
     }
 }
 
-// Create a mapping from a bag name to all bags that can directly contain it.
-fn create_map(rules: &Rules) -> HashMap<String, HashSet<String>> {
-    let mut bag_to_parents: HashMap<String, HashSet<String>> = HashMap::new();
-    for (parent, contents) in rules.rules.iter() {
-        for c in contents.bags.iter() {
-            if !bag_to_parents.contains_key(&c.name) {
-                bag_to_parents.insert(c.name.clone(), HashSet::new());
+// Questions that can be asked about bag rules. Grammar, one query per line:
+//
+// paths <bag> to <bag>     all ways the first bag can (indirectly) contain the second one
+// shortest <bag> to <bag>  fewest nested bags from the first bag down to the second one
+// depth <bag>              deepest nesting inside the bag
+// empty                    bags that contain nothing
+// largest                  bag with the most bags inside
+#[derive(Debug, PartialEq)]
+enum Query {
+    Paths(String, String),
+    Shortest(String, String),
+    Depth(String),
+    Empty,
+    Largest,
+}
+
+impl Query {
+    fn parse(line: &str) -> Result<Query, String> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let two_bags = |words: &[&str]| -> Result<(String, String), String> {
+            let to = words
+                .iter()
+                .position(|&w| w == "to")
+                .ok_or_else(|| String::from("expected \"<bag> to <bag>\""))?;
+            let (from, to) = (words[..to].join(" "), words[to + 1..].join(" "));
+            if from.is_empty() || to.is_empty() {
+                return Err(String::from("expected \"<bag> to <bag>\""));
             }
-            bag_to_parents
-                .get_mut(&c.name)
-                .unwrap()
-                .insert(parent.clone());
+            Ok((from, to))
+        };
+        match words.split_first() {
+            Some((&"paths", rest)) => two_bags(rest).map(|(a, b)| Query::Paths(a, b)),
+            Some((&"shortest", rest)) => two_bags(rest).map(|(a, b)| Query::Shortest(a, b)),
+            Some((&"depth", rest)) if !rest.is_empty() => Ok(Query::Depth(rest.join(" "))),
+            Some((&"empty", [])) => Ok(Query::Empty),
+            Some((&"largest", [])) => Ok(Query::Largest),
+            _ => Err(format!("can't parse query {:?}", line)),
         }
     }
-    bag_to_parents
 }
 
-fn get_outer_bags(map: &HashMap<String, HashSet<String>>, bag: &str) -> HashSet<String> {
-    // "shiny gold" -> set of parents
-    // set of parents -> superset of parents
-    let mut parents = map[bag].clone();
-    println!("parents of {:?}: {:?}", bag, parents);
-    let mut queue = parents.clone();
-    while !queue.is_empty() {
-        let p = queue.iter().nth(0).unwrap().clone();
-        queue.remove(&p);
-        println!("queue={:?} p={:?}", queue, p);
-        if !map.contains_key(&p) {
-            continue;
+// Result of a query: a short answer plus chains of bags (outermost first) that back it up.
+#[derive(Debug)]
+struct Answer {
+    summary: String,
+    paths: Vec<Vec<usize>>,
+}
+
+// Stop listing paths after this many, there can be exponentially many of them.
+const MAX_PATHS: usize = 1000;
+
+impl BagGraph {
+    fn answer(&self, query: &Query) -> Result<Answer, GraphError> {
+        match query {
+            Query::Paths(from, to) => {
+                let paths = self.all_paths(self.node(from)?, self.node(to)?);
+                let summary = if paths.len() >= MAX_PATHS {
+                    format!("at least {} paths", paths.len())
+                } else {
+                    format!("{} paths", paths.len())
+                };
+                Ok(Answer { summary, paths })
+            }
+            Query::Shortest(from, to) => {
+                Ok(match self.shortest_path(self.node(from)?, self.node(to)?) {
+                    Some(path) => Answer {
+                        summary: format!("{} bags", path.len()),
+                        paths: vec![path],
+                    },
+                    None => Answer {
+                        summary: format!("{:?} can't contain {:?}", from, to),
+                        paths: vec![],
+                    },
+                })
+            }
+            Query::Depth(bag) => {
+                let path = self.deepest_path(self.node(bag)?)?;
+                Ok(Answer {
+                    summary: format!("depth {}", path.len() - 1),
+                    paths: vec![path],
+                })
+            }
+            Query::Empty => {
+                let empty = (0..self.names.len())
+                    .filter(|&n| self.children[n].is_empty())
+                    .map(|n| self.names[n].as_str())
+                    .collect::<Vec<&str>>();
+                Ok(Answer {
+                    summary: empty.join(", "),
+                    paths: vec![],
+                })
+            }
+            Query::Largest => {
+                let counts = self.all_inner_bag_counts()?;
+                Ok(
+                    match (0..self.names.len()).max_by_key(|&n| (counts[n], std::cmp::Reverse(n))) {
+                        Some(n) => Answer {
+                            summary: format!("{} with {} bags inside", self.names[n], counts[n]),
+                            paths: self.children[n]
+                                .iter()
+                                .map(|&(child, _)| vec![n, child])
+                                .collect(),
+                        },
+                        None => Answer {
+                            summary: String::from("no bags"),
+                            paths: vec![],
+                        },
+                    },
+                )
+            }
         }
-        for pp in map.get(&p).unwrap() {
-            if !parents.contains(pp) {
-                println!("adding {:?} to parents & queue", pp);
-                parents.insert(pp.clone());
-                queue.insert(pp.clone());
+    }
+
+    // Lists up to MAX_PATHS chains from one bag down to another.
+    fn all_paths(&self, from: usize, to: usize) -> Vec<Vec<usize>> {
+        let mut can_reach = self.outer_nodes(to);
+        can_reach.insert(to);
+        let mut paths = vec![];
+        // (node, index of the next child to look at)
+        let mut stack = vec![(from, 0)];
+        while let Some(&(node, next)) = stack.last() {
+            if node == to && stack.len() > 1 {
+                paths.push(stack.iter().map(|&(n, _)| n).collect());
+                if paths.len() >= MAX_PATHS {
+                    break;
+                }
+                stack.pop();
+                continue;
+            }
+            match self.children[node].get(next) {
+                Some(&(child, _)) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    if can_reach.contains(&child) && !stack.iter().any(|&(n, _)| n == child) {
+                        stack.push((child, 0));
+                    }
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        paths
+    }
+
+    // Finds the chain with the fewest bags from one bag down to another (BFS).
+    fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut came_from = HashMap::new();
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back(from);
+        while let Some(node) = queue.pop_front() {
+            for &(child, _) in self.children[node].iter() {
+                if child == from || came_from.contains_key(&child) {
+                    continue;
+                }
+                came_from.insert(child, node);
+                if child == to {
+                    let mut path = vec![to];
+                    while *path.last().unwrap() != from {
+                        path.push(came_from[path.last().unwrap()]);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(child);
+            }
+        }
+        None
+    }
+
+    // Finds the longest chain of bags nested inside given bag, starting with the bag itself.
+    fn deepest_path(&self, root: usize) -> Result<Vec<usize>, GraphError> {
+        // Longest chain length below each node and the child it goes through.
+        let mut depth = vec![(0, None); self.names.len()];
+        for &node in self.topological_order()?.iter().rev() {
+            if let Some(&(child, _)) = self.children[node]
+                .iter()
+                .max_by_key(|&&(child, _)| (depth[child].0, std::cmp::Reverse(child)))
+            {
+                depth[node] = (depth[child].0 + 1, Some(child));
+            }
+        }
+        let mut path = vec![root];
+        while let (_, Some(child)) = depth[*path.last().unwrap()] {
+            path.push(child);
+        }
+        Ok(path)
+    }
+
+    // Counts bags inside every bag.
+    fn all_inner_bag_counts(&self) -> Result<Vec<BagCount>, GraphError> {
+        let mut inside = vec![0 as BagCount; self.names.len()];
+        for &node in self.topological_order()?.iter().rev() {
+            let mut total = 0 as BagCount;
+            for &(child, count) in self.children[node].iter() {
+                total = inside[child]
+                    .checked_add(1)
+                    .and_then(|c| c.checked_mul(count as BagCount))
+                    .and_then(|c| c.checked_add(total))
+                    .ok_or_else(|| GraphError::Overflow(self.names[node].clone()))?;
             }
+            inside[node] = total;
         }
+        Ok(inside)
     }
 
-    parents
+    // Formats a chain of bags with counts along the way:
+    // shiny gold -2-> dark red -2-> dark orange
+    fn path_to_string(&self, path: &[usize]) -> String {
+        let mut out = self.names[path[0]].clone();
+        for pair in path.windows(2) {
+            let (_, count) = self.children[pair[0]]
+                .iter()
+                .find(|&&(child, _)| child == pair[1])
+                .unwrap();
+            out.push_str(&format!(" -{}-> {}", count, self.names[pair[1]]));
+        }
+        out
+    }
 }
 
-fn calculate_inner_bag_counts(rules: &Rules, bag: &str, counts: &mut HashMap<String, u32>) -> u32 {
-    if counts.contains_key(bag) {
-        return counts[bag];
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(s: &str) -> Rules {
+        let (rules, errors) = Rules::parse(s);
+        assert_eq!(errors, vec![]);
+        rules
     }
-    let mut cnt = 1;
 
-    let rule = rules.rules.get(bag);
-    if rule.is_some() {
-        for bag_and_count in rule.unwrap().bags.iter() {
-            cnt += bag_and_count.count
-                * calculate_inner_bag_counts(rules, bag_and_count.name.as_str(), counts);
+    const SIMPLE: &str = "\
+light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.
+";
+
+    #[test]
+    fn bag_graph_works() {
+        let graph = BagGraph::from_rules(&parse_ok(SIMPLE));
+        assert_eq!(graph.outer_bags("shiny gold").unwrap().len(), 4);
+        assert_eq!(graph.inner_bag_count("shiny gold"), Ok(32));
+        assert!(graph.outer_bags("light red").unwrap().is_empty());
+        assert_eq!(
+            graph.inner_bag_count("plaid nope"),
+            Err(GraphError::UnknownBag(String::from("plaid nope")))
+        );
+        let order = graph.topological_order().unwrap();
+        assert_eq!(order.len(), graph.names.len());
+    }
+
+    #[test]
+    fn queries_work() {
+        let graph = BagGraph::from_rules(&parse_ok(SIMPLE));
+        let ask = |q: &str| graph.answer(&Query::parse(q).unwrap()).unwrap();
+        let paths = |a: &Answer| {
+            a.paths
+                .iter()
+                .map(|p| graph.path_to_string(p))
+                .collect::<Vec<String>>()
+        };
+
+        let answer = ask("paths light red to shiny gold");
+        assert_eq!(answer.summary, "2 paths");
+        assert_eq!(
+            paths(&answer),
+            vec![
+                "light red -1-> bright white -1-> shiny gold",
+                "light red -2-> muted yellow -2-> shiny gold"
+            ]
+        );
+        assert_eq!(
+            paths(&ask("shortest dark orange to faded blue")),
+            vec!["dark orange -4-> muted yellow -9-> faded blue"]
+        );
+        assert_eq!(ask("depth light red").summary, "depth 4");
+        assert_eq!(ask("empty").summary, "dotted black, faded blue");
+        assert_eq!(ask("largest").summary, "dark orange with 406 bags inside");
+        assert_eq!(ask("shortest faded blue to shiny gold").paths.len(), 0);
+        assert!(Query::parse("paths shiny gold").is_err());
+    }
+
+    #[test]
+    fn inner_bag_breakdown_works() {
+        let graph = BagGraph::from_rules(&parse_ok(SIMPLE));
+        let breakdown = graph.inner_bag_breakdown("shiny gold").unwrap();
+        assert_eq!(
+            breakdown,
+            vec![
+                (String::from("dark olive"), 1),
+                (String::from("dotted black"), 16),
+                (String::from("faded blue"), 13),
+                (String::from("vibrant plum"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_nesting_does_not_overflow() {
+        // Each level holds 1000 bags of the next one: 1000^12 bags at the bottom, more than
+        // fits into u32 or u64.
+        let mut rules = String::new();
+        for i in 0..12 {
+            rules.push_str(&format!(
+                "level l{} bags contain 1000 level l{} bags.\n",
+                i,
+                i + 1
+            ));
         }
+        let graph = BagGraph::from_rules(&parse_ok(&rules));
+        let count = graph.inner_bag_count("level l0").unwrap();
+        assert_eq!(count, (1..=12).map(|i| 1000u128.pow(i)).sum());
+
+        // Levels 13 and 14 hold 1000^13 and 1000^14 bags, 1000^13 is already more than u128 can
+        // hold.
+        for i in 12..14 {
+            rules.push_str(&format!(
+                "level l{} bags contain 1000 level l{} bags.\n",
+                i,
+                i + 1
+            ));
+        }
+        let graph = BagGraph::from_rules(&parse_ok(&rules));
+        assert_eq!(
+            graph.inner_bag_count("level l0"),
+            Err(GraphError::Overflow(String::from("level l0")))
+        );
     }
 
-    counts.insert(String::from(bag), cnt);
+    #[test]
+    fn parse_accepts_any_colour_length() {
+        let rules = parse_ok(
+            "red bags contain 1 very dark shiny blue bag, 2 red bags.\n\
+             very dark shiny blue bags contain no other bags.\n",
+        );
+        let bags = &rules.rules["red"].bags;
+        assert_eq!(bags[0].name, "very dark shiny blue");
+        assert_eq!(bags[1].count, 2);
+        assert!(rules.rules["very dark shiny blue"].bags.is_empty());
+    }
 
-    cnt
+    #[test]
+    fn parse_reports_every_error() {
+        let (rules, errors) = Rules::parse(
+            "shiny gold bags contain 2 dark red bags, x muted blue bags, 3 plaid bugs.\n\
+             dark red bags contain no other bags.\n\
+             dark red bags contain no other bags.\n\
+             faded blue bags contain no other bag\n\
+             what is this\n",
+        );
+        assert_eq!(rules.rules.len(), 1);
+        let found = errors
+            .iter()
+            .map(|e| (e.line, e.column, e.fragment.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (1, 42, "x muted blue bags"),
+                (1, 61, "3 plaid bugs"),
+                (3, 1, "dark red"),
+                (4, 37, ""),
+                (4, 25, "no other bag"),
+                (5, 1, "what is this"),
+            ]
+        );
+    }
+
+    #[test]
+    fn cycle_is_error() {
+        let graph = BagGraph::from_rules(&parse_ok(
+            "shiny gold bags contain 1 dark red bag.\n\
+             dark red bags contain 2 shiny gold bags, 1 faded blue bag.\n",
+        ));
+        let cycle = vec!["dark red", "shiny gold", "dark red"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(graph.find_cycle(), Some(cycle));
+        assert!(matches!(
+            graph.inner_bag_count("shiny gold"),
+            Err(GraphError::Cycle(_))
+        ));
+    }
 }
 
+// Usage: day7 [FILE [dot | query [QUERY]]]
+//
+// With "dot", prints the rules as a Graphviz graph instead of solving the puzzle. With "query",
+// answers QUERY (see Query), or queries read from stdin one per line.
 fn main() {
-    let contents = std::fs::read_to_string("input/7.txt").expect("read failed");
-    let rules = Rules::parse(&contents);
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/7.txt"));
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let (rules, errors) = Rules::parse(&contents);
+    for e in errors.iter() {
+        eprintln!("{}", e);
+    }
     // dbg!(rules);
-    let map = create_map(&rules);
-    let outer_bags = get_outer_bags(&map, "shiny gold");
-    dbg!(&outer_bags);
-    dbg!(outer_bags.len());
+    let graph = BagGraph::from_rules(&rules);
+    if std::env::args().nth(2).as_deref() == Some("dot") {
+        print!("{}", graph.to_dot());
+        return;
+    }
+    if let Some(cycle) = graph.find_cycle() {
+        eprintln!("{}", GraphError::Cycle(cycle));
+        std::process::exit(1);
+    }
+    if std::env::args().nth(2).as_deref() == Some("query") {
+        let queries = match std::env::args().nth(3) {
+            Some(q) => vec![q],
+            None => std::io::stdin()
+                .lines()
+                .map(|line| line.expect("read failed"))
+                .collect(),
+        };
+        for q in queries.iter().filter(|q| !q.trim().is_empty()) {
+            match Query::parse(q).map(|q| graph.answer(&q)) {
+                Ok(Ok(answer)) => {
+                    println!("{}", answer.summary);
+                    for path in answer.paths.iter() {
+                        println!("  {}", graph.path_to_string(path));
+                    }
+                }
+                Ok(Err(e)) => eprintln!("{}", e),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        return;
+    }
 
-    let mut counts = HashMap::new();
-    dbg!(calculate_inner_bag_counts(&rules, "shiny gold", &mut counts) - 1);
+    match graph.outer_bags("shiny gold") {
+        Ok(outer_bags) => {
+            dbg!(&outer_bags);
+            dbg!(outer_bags.len());
+        }
+        Err(e) => eprintln!("{}", e),
+    }
+    match graph.inner_bag_breakdown("shiny gold") {
+        Ok(breakdown) => {
+            for (name, count) in breakdown.iter() {
+                println!("{:>8} {}", count, name);
+            }
+        }
+        Err(e) => eprintln!("{}", e),
+    }
+    match graph.inner_bag_count("shiny gold") {
+        Ok(count) => {
+            dbg!(count);
+        }
+        Err(e) => eprintln!("{}", e),
+    }
 }
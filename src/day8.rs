@@ -1,40 +1,313 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 type ArgumentType = i32;
 
-#[derive(Debug, Clone)]
+// Index into Handheld::regs.
+type Register = usize;
+
+// Register names, in index order. "acc" is the accumulator of the original three-opcode machine.
+const REGISTER_NAMES: [&str; 8] = ["acc", "r1", "r2", "r3", "r4", "r5", "r6", "r7"];
+const ACC: Register = 0;
+
+// Source of a value: a register or an immediate number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Reg(Register),
+    Imm(ArgumentType),
+}
+
+// Condition of a conditional jump, comparing a register to 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cond {
+    Zero,
+    NonZero,
+    Positive,
+    Negative,
+}
+
+// Instruction set. Jumps are relative to the jumping instruction, by Op::arg.
+//
+// acc N        acc += N
+// nop N        nothing
+// jmp N        jump by N
+// set R X      R = X (X is a register or a number)
+// add R X      R += X
+// sub R X      R -= X
+// mul R X      R *= X
+// jz R N       jump by N if R == 0 (also jnz: R != 0, jgz: R > 0, jlz: R < 0)
+// call N       push address of the next instruction, jump by N
+// ret          pop an address and jump there; with an empty stack, end the program
+#[derive(Debug, Clone, PartialEq)]
 enum OpType {
     Acc,
     Jmp,
     Nop,
+    Set(Register, Operand),
+    Add(Register, Operand),
+    Sub(Register, Operand),
+    Mul(Register, Operand),
+    Jcc(Cond, Register),
+    Call,
+    Ret,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Op {
     op_type: OpType,
+    // Number for acc and nop, jump offset for jumps and call, 0 otherwise.
     arg: ArgumentType,
 }
 
-impl Op {
-    // Parses a string like "acc +17" into an Op.
-    fn parse(line: &str) -> Op {
-        let (cmd, arg_str) = line.split_at(4);
-        let op_type = match cmd.trim() {
-            "nop" => OpType::Nop,
-            "acc" => OpType::Acc,
-            "jmp" => OpType::Jmp,
-            _ => {
-                panic!("invalid cmd {}", cmd);
+const COND_MNEMONICS: [(&str, Cond); 4] = [
+    ("jz", Cond::Zero),
+    ("jnz", Cond::NonZero),
+    ("jgz", Cond::Positive),
+    ("jlz", Cond::Negative),
+];
+
+fn parse_register(s: &str) -> Result<Register, String> {
+    REGISTER_NAMES
+        .iter()
+        .position(|&r| r == s)
+        .ok_or_else(|| format!("unknown register {:?}", s))
+}
+
+fn parse_number(s: &str) -> Result<ArgumentType, String> {
+    s.strip_prefix('+')
+        .unwrap_or(s)
+        .parse::<ArgumentType>()
+        .map_err(|_| format!("bad number {:?}", s))
+}
+
+fn parse_operand(s: &str) -> Result<Operand, String> {
+    parse_register(s)
+        .map(Operand::Reg)
+        .or_else(|_| parse_number(s).map(Operand::Imm))
+        .map_err(|_| format!("expected a register or a number: {:?}", s))
+}
+
+// Line of assembly source that couldn't be assembled. Line numbers are 1-based.
+#[derive(Debug, PartialEq)]
+struct AsmError {
+    line: usize,
+    message: String,
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// Turns assembly source into instructions, e.g., "acc +17" into an Op. On top of what
+// disassemble() prints, source can have comments (from ';' to the end of the line), blank lines
+// and labels ("name:" before an instruction or on its own line), which jumps and calls can use
+// instead of offsets:
+//
+// loop:  add r1 -1   ; count down
+//        jnz r1 loop
+fn assemble(source: &str) -> Result<Vec<Op>, AsmError> {
+    // First pass: strip comments and labels, remember where labels point.
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let error = |message| AsmError {
+            line: i + 1,
+            message,
+        };
+        let mut code = line.split(';').next().unwrap().trim();
+        while let Some(colon) = code.find(':') {
+            let label = code[..colon].trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(error(format!("bad label {:?}", label)));
             }
+            // Jump targets are looked up as labels first, so these would change their meaning.
+            if parse_number(label).is_ok() || parse_register(label).is_ok() {
+                return Err(error(format!(
+                    "label {:?} looks like a number or register",
+                    label
+                )));
+            }
+            if labels.insert(String::from(label), lines.len()).is_some() {
+                return Err(error(format!("label {:?} defined twice", label)));
+            }
+            code = code[colon + 1..].trim();
+        }
+        if !code.is_empty() {
+            lines.push((i + 1, code));
+        }
+    }
+
+    // Second pass: parse instructions.
+    let mut ops = vec![];
+    for (ip, &(line, code)) in lines.iter().enumerate() {
+        let words = code.split_whitespace().collect::<Vec<&str>>();
+        let target = |s: &str| -> Result<ArgumentType, String> {
+            match labels.get(s) {
+                Some(&to) => Ok(to as ArgumentType - ip as ArgumentType),
+                None => parse_number(s).map_err(|_| format!("unknown label {:?}", s)),
+            }
+        };
+        let arith = |words: &[&str], f: fn(Register, Operand) -> OpType| match words {
+            [r, x] => Ok(Op {
+                op_type: f(parse_register(r)?, parse_operand(x)?),
+                arg: 0,
+            }),
+            _ => Err(String::from("expected a register and a value")),
+        };
+        let op = match words.split_first() {
+            Some((&"acc", [n])) => parse_number(n).map(|arg| Op {
+                op_type: OpType::Acc,
+                arg,
+            }),
+            Some((&"nop", [n])) => parse_number(n).map(|arg| Op {
+                op_type: OpType::Nop,
+                arg,
+            }),
+            Some((&"jmp", [t])) => target(t).map(|arg| Op {
+                op_type: OpType::Jmp,
+                arg,
+            }),
+            Some((&"call", [t])) => target(t).map(|arg| Op {
+                op_type: OpType::Call,
+                arg,
+            }),
+            Some((&"ret", [])) => Ok(Op {
+                op_type: OpType::Ret,
+                arg: 0,
+            }),
+            Some((&"set", rest)) => arith(rest, OpType::Set),
+            Some((&"add", rest)) => arith(rest, OpType::Add),
+            Some((&"sub", rest)) => arith(rest, OpType::Sub),
+            Some((&"mul", rest)) => arith(rest, OpType::Mul),
+            Some((m, rest)) => match (COND_MNEMONICS.iter().find(|(c, _)| c == m), rest) {
+                (Some(&(_, cond)), [r, t]) => parse_register(r).and_then(|r| {
+                    Ok(Op {
+                        op_type: OpType::Jcc(cond, r),
+                        arg: target(t)?,
+                    })
+                }),
+                (Some(_), _) => Err(String::from("expected a register and a target")),
+                (None, _) => Err(format!("bad instruction {:?}", code)),
+            },
+            None => unreachable!(),
         };
-        let arg = arg_str.parse::<ArgumentType>().unwrap();
-        Op { op_type, arg }
+        ops.push(op.map_err(|message| AsmError { line, message })?);
+    }
+    Ok(ops)
+}
+
+// Prints instructions in the format assemble() reads, one per line, using offsets for jumps.
+// Original three-opcode programs come out exactly as the puzzle input has them.
+fn disassemble(ops: &[Op]) -> String {
+    ops.iter().map(|op| format!("{}\n", op)).collect()
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Reg(r) => write!(f, "{}", REGISTER_NAMES[*r]),
+            Operand::Imm(n) => write!(f, "{}", n),
+        }
     }
 }
 
 impl std::fmt::Display for Op {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?} {}", self.op_type, self.arg)
+        use OpType::*;
+
+        match &self.op_type {
+            Acc => write!(f, "acc {:+}", self.arg),
+            Jmp => write!(f, "jmp {:+}", self.arg),
+            Nop => write!(f, "nop {:+}", self.arg),
+            Set(r, x) => write!(f, "set {} {}", REGISTER_NAMES[*r], x),
+            Add(r, x) => write!(f, "add {} {}", REGISTER_NAMES[*r], x),
+            Sub(r, x) => write!(f, "sub {} {}", REGISTER_NAMES[*r], x),
+            Mul(r, x) => write!(f, "mul {} {}", REGISTER_NAMES[*r], x),
+            Jcc(cond, r) => {
+                let (m, _) = COND_MNEMONICS.iter().find(|(_, c)| c == cond).unwrap();
+                write!(f, "{} {} {:+}", m, REGISTER_NAMES[*r], self.arg)
+            }
+            Call => write!(f, "call {:+}", self.arg),
+            Ret => write!(f, "ret"),
+        }
+    }
+}
+
+// What arithmetic does when the result doesn't fit into a register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OverflowPolicy {
+    Wrap,
+    Saturate,
+    // Stop the machine with RunStatus::Overflow.
+    Error,
+}
+
+impl OverflowPolicy {
+    // Turns an exact result into what ends up in the register, None for an error.
+    fn apply(self, exact: i64) -> Option<ArgumentType> {
+        let min = ArgumentType::MIN as i64;
+        let max = ArgumentType::MAX as i64;
+        match self {
+            _ if exact >= min && exact <= max => Some(exact as ArgumentType),
+            OverflowPolicy::Wrap => Some(exact as ArgumentType),
+            OverflowPolicy::Saturate => Some(exact.clamp(min, max) as ArgumentType),
+            OverflowPolicy::Error => None,
+        }
+    }
+}
+
+// Limits for Handheld::run(). None means no limit.
+#[derive(Debug, Clone)]
+struct Limits {
+    max_steps: Option<u64>,
+    max_time: Option<std::time::Duration>,
+    // Deepest allowed call stack.
+    max_stack: Option<usize>,
+    overflow: OverflowPolicy,
+    // Stop before executing an instruction for the second time.
+    stop_on_repeat: bool,
+}
+
+impl Default for Limits {
+    // Stops on repeats (so every program stops), wraps on overflow.
+    fn default() -> Limits {
+        Limits {
+            max_steps: None,
+            max_time: None,
+            max_stack: None,
+            overflow: OverflowPolicy::Wrap,
+            stop_on_repeat: true,
+        }
+    }
+}
+
+// Why a run stopped.
+#[derive(Debug, Clone, PartialEq)]
+enum RunStatus {
+    Terminated,
+    // About to execute the instruction at ip for the second time.
+    Looped(ArgumentType),
+    // ip points outside the program.
+    OutOfBounds(i64),
+    StepLimit,
+    TimeLimit,
+    StackLimit,
+    // Arithmetic in the instruction at ip overflowed.
+    Overflow(ArgumentType),
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunStatus::Terminated => write!(f, "terminated"),
+            RunStatus::Looped(ip) => write!(f, "looped at {}", ip),
+            RunStatus::OutOfBounds(ip) => write!(f, "ip out of bounds: {}", ip),
+            RunStatus::StepLimit => write!(f, "step limit reached"),
+            RunStatus::TimeLimit => write!(f, "time limit reached"),
+            RunStatus::StackLimit => write!(f, "stack limit reached"),
+            RunStatus::Overflow(ip) => write!(f, "overflow at {}", ip),
+        }
     }
 }
 
@@ -42,53 +315,141 @@ impl std::fmt::Display for Op {
 struct Handheld {
     instructions: Vec<Op>,
     ip: ArgumentType,
-    acc: ArgumentType,
+    // Indexed by Register.
+    regs: [ArgumentType; REGISTER_NAMES.len()],
+    // Return addresses of calls.
+    stack: Vec<ArgumentType>,
     seen: HashSet<ArgumentType>,
 }
 
 impl Handheld {
-    fn parse(lines: &str) -> Handheld {
-        let instructions = lines
-            .lines()
-            .map(|line| Op::parse(line))
-            .collect::<Vec<Op>>();
+    fn new(instructions: Vec<Op>) -> Handheld {
         Handheld {
             instructions,
             ip: 0,
-            acc: 0,
+            regs: [0; REGISTER_NAMES.len()],
+            stack: vec![],
             seen: HashSet::new(),
         }
     }
 
-    // Does a step unless about to execute an instruction for the second time or we are at the end
-    // of the program.
-    fn do_step(self: &mut Self, check_repeat: bool) -> bool {
-        use OpType::*;
+    fn parse(lines: &str) -> Result<Handheld, AsmError> {
+        assemble(lines).map(Handheld::new)
+    }
 
-        if (self.ip < 0) || ((self.ip as usize) >= self.instructions.len()) {
-            panic!("IP out of bounds: {}", self.ip);
-        }
+    fn acc(&self) -> ArgumentType {
+        self.regs[ACC]
+    }
 
-        if check_repeat && self.seen.contains(&self.ip) {
-            return false;
+    fn value(&self, x: Operand) -> ArgumentType {
+        match x {
+            Operand::Reg(r) => self.regs[r],
+            Operand::Imm(n) => n,
         }
+    }
 
-        self.seen.insert(self.ip);
+    // Executes one instruction. If that can't be done (the program has ended, ip is out of
+    // bounds or arithmetic overflowed with OverflowPolicy::Error), returns why and leaves the
+    // machine as it was.
+    fn try_step(&mut self, overflow: OverflowPolicy) -> Result<(), RunStatus> {
+        use OpType::*;
+
+        if self.terminated() {
+            return Err(RunStatus::Terminated);
+        }
+        if (self.ip < 0) || ((self.ip as usize) >= self.instructions.len()) {
+            return Err(RunStatus::OutOfBounds(self.ip as i64));
+        }
 
         let op = &self.instructions[self.ip as usize];
+        let ip = self.ip;
+        let arith = |a: ArgumentType, b: ArgumentType, f: fn(i64, i64) -> i64| {
+            overflow
+                .apply(f(a as i64, b as i64))
+                .ok_or(RunStatus::Overflow(ip))
+        };
+        // Jumps that don't fit into ip are out of bounds anyway.
+        let jump = |by: ArgumentType| {
+            ip.checked_add(by)
+                .ok_or(RunStatus::OutOfBounds(ip as i64 + by as i64))
+        };
+        let mut next_ip = ip + 1;
         match op.op_type {
             Nop => {}
-            Acc => {
-                self.acc += op.arg;
+            Acc => self.regs[ACC] = arith(self.regs[ACC], op.arg, |a, b| a + b)?,
+            Jmp => next_ip = jump(op.arg)?,
+            Set(r, x) => self.regs[r] = self.value(x),
+            Add(r, x) => self.regs[r] = arith(self.regs[r], self.value(x), |a, b| a + b)?,
+            Sub(r, x) => self.regs[r] = arith(self.regs[r], self.value(x), |a, b| a - b)?,
+            Mul(r, x) => self.regs[r] = arith(self.regs[r], self.value(x), |a, b| a * b)?,
+            Jcc(cond, r) => {
+                let v = self.regs[r];
+                let taken = match cond {
+                    Cond::Zero => v == 0,
+                    Cond::NonZero => v != 0,
+                    Cond::Positive => v > 0,
+                    Cond::Negative => v < 0,
+                };
+                if taken {
+                    next_ip = jump(op.arg)?;
+                }
+            }
+            Call => {
+                next_ip = jump(op.arg)?;
+                self.stack.push(ip + 1);
+            }
+            Ret => {
+                next_ip = self
+                    .stack
+                    .pop()
+                    .unwrap_or(self.instructions.len() as ArgumentType);
             }
-            Jmp => {}
-        };
-        self.ip += match op.op_type {
-            Jmp => op.arg,
-            _ => 1,
         };
+        self.seen.insert(ip);
+        self.ip = next_ip;
 
-        true
+        Ok(())
+    }
+
+    // Runs until the program ends or one of the limits is hit. Returns why it stopped and how many
+    // instructions were executed.
+    fn run(&mut self, limits: &Limits) -> (RunStatus, u64) {
+        let start = std::time::Instant::now();
+        let mut steps = 0;
+        loop {
+            if limits.stop_on_repeat && self.seen.contains(&self.ip) && !self.terminated() {
+                return (RunStatus::Looped(self.ip), steps);
+            }
+            if limits.max_steps.is_some_and(|max| steps >= max) {
+                return (RunStatus::StepLimit, steps);
+            }
+            // Checking the clock is slow, only do it every now and then.
+            if steps % 1024 == 0 && limits.max_time.is_some_and(|max| start.elapsed() >= max) {
+                return (RunStatus::TimeLimit, steps);
+            }
+            // Stop before a call that would make the stack deeper than allowed.
+            let calls = self
+                .instructions
+                .get(self.ip as usize)
+                .is_some_and(|op| op.op_type == OpType::Call);
+            if calls && limits.max_stack.is_some_and(|max| self.stack.len() >= max) {
+                return (RunStatus::StackLimit, steps);
+            }
+            if let Err(status) = self.try_step(limits.overflow) {
+                return (status, steps);
+            }
+            steps += 1;
+        }
+    }
+
+    // Looks up a machine variable by name, for debugger expressions: ip, sp (stack depth) or a
+    // register.
+    fn var(&self, name: &str) -> Option<i64> {
+        match name {
+            "ip" => Some(self.ip as i64),
+            "sp" => Some(self.stack.len() as i64),
+            _ => parse_register(name).ok().map(|r| self.regs[r] as i64),
+        }
     }
 
     fn terminated(self: &Self) -> bool {
@@ -96,62 +457,1449 @@ impl Handheld {
     }
 
     fn pretty_print_state(self: &Self) -> String {
+        let regs = self
+            .regs
+            .iter()
+            .enumerate()
+            .filter(|&(r, &v)| r == ACC || v != 0)
+            .map(|(r, v)| format!("{}: {}", REGISTER_NAMES[r], v))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let stack = if self.stack.is_empty() {
+            String::new()
+        } else {
+            format!(" stack: {:?}", self.stack)
+        };
         if self.terminated() {
-            format!("ip: {} acc: {} next: TERMINATED", self.ip, self.acc)
+            format!("ip: {} {}{} next: TERMINATED", self.ip, regs, stack)
         } else {
             format!(
-                "ip: {} acc: {} next: {}",
-                self.ip, self.acc, self.instructions[self.ip as usize]
+                "ip: {} {}{} next: {}",
+                self.ip, regs, stack, self.instructions[self.ip as usize]
             )
         }
     }
 
-    fn run_until_repeats(self: &mut Self) {
-        while self.do_step(true) {
-            println!("{}", self.pretty_print_state());
+    // Finds every jmp <-> nop flip that makes the program terminate, and what acc ends up as
+    // with each of them.
+    fn find_broken_instr(self: &mut Self) -> Result<Vec<(Repair, ArgumentType)>, String> {
+        let repairs = find_repairs(&self.instructions)?;
+        Ok(repairs
+            .into_iter()
+            .map(|repair| {
+                let mut handheld_tmp = self.clone();
+                handheld_tmp.instructions[repair.ip].op_type = repair.op_type.clone();
+                // Guaranteed to terminate, see find_repairs().
+                handheld_tmp.run(&Limits::default());
+                (repair, handheld_tmp.acc())
+            })
+            .collect())
+    }
+}
+
+// Control-flow graph of a program whose jumps don't depend on data, i.e., one without
+// conditional jumps, calls and returns. Node instructions.len() is the end of the program.
+struct Cfg {
+    // Indexed by instruction: where execution continues, None if that's out of bounds.
+    next: Vec<Option<usize>>,
+    // Indexed by node: whether execution from there gets to the end.
+    terminates: Vec<bool>,
+}
+
+impl Cfg {
+    // Where execution continues after op at ip, None if that's out of bounds.
+    fn successor(op: &Op, ip: usize, len: usize) -> Option<usize> {
+        let to = match op.op_type {
+            OpType::Jmp => ip as i64 + op.arg as i64,
+            _ => ip as i64 + 1,
+        };
+        if to >= 0 && to <= len as i64 {
+            Some(to as usize)
+        } else {
+            None
         }
     }
 
-    fn run_until_end(self: &mut Self) -> bool {
-        while self.do_step(true) {
-            println!("{}", self.pretty_print_state());
-            if self.terminated() {
-                println!("got to the end :)");
-                return true;
+    fn build(ops: &[Op]) -> Result<Cfg, String> {
+        use OpType::*;
+
+        if let Some(ip) = ops
+            .iter()
+            .position(|op| matches!(op.op_type, Jcc(..) | Call | Ret))
+        {
+            return Err(format!(
+                "control flow depends on data at {}: {}",
+                ip, ops[ip]
+            ));
+        }
+        let len = ops.len();
+        let next = ops
+            .iter()
+            .enumerate()
+            .map(|(ip, op)| Cfg::successor(op, ip, len))
+            .collect::<Vec<Option<usize>>>();
+
+        // Walk backwards from the end: whatever leads to a terminating node terminates too.
+        let mut preds = vec![vec![]; len + 1];
+        for (ip, n) in next.iter().enumerate() {
+            if let Some(n) = n {
+                preds[*n].push(ip);
             }
         }
-        println!("repeated :(");
-        false
+        let mut terminates = vec![false; len + 1];
+        terminates[len] = true;
+        let mut queue = vec![len];
+        while let Some(node) = queue.pop() {
+            for &p in preds[node].iter() {
+                if !terminates[p] {
+                    terminates[p] = true;
+                    queue.push(p);
+                }
+            }
+        }
+        Ok(Cfg { next, terminates })
     }
+}
 
-    fn find_broken_instr(self: &mut Self) -> ArgumentType {
-        use OpType::*;
+// Replacing the instruction at ip with op_type (keeping its argument) fixes the program.
+#[derive(Debug, PartialEq)]
+struct Repair {
+    ip: usize,
+    op_type: OpType,
+}
 
-        for (i, op) in self.instructions.iter().enumerate() {
-            let new_op = match op.op_type {
-                Jmp => Nop,
-                Nop => Jmp,
-                _ => {
-                    continue;
+// Finds every single jmp <-> nop flip that makes the program terminate, in linear time. Only
+// instructions that the unmodified program executes can matter, and flipping one of them fixes
+// the program iff its new successor terminates. (The path from there never comes back to the
+// flipped instruction: that one doesn't terminate unflipped.) Empty if the program already
+// terminates.
+fn find_repairs(ops: &[Op]) -> Result<Vec<Repair>, String> {
+    use OpType::*;
+
+    let cfg = Cfg::build(ops)?;
+    let mut repairs = vec![];
+    let mut visited = vec![false; ops.len()];
+    let mut ip = 0;
+    while ip < ops.len() && !visited[ip] {
+        visited[ip] = true;
+        let flip = match ops[ip].op_type {
+            Jmp => Some(Nop),
+            Nop => Some(Jmp),
+            _ => None,
+        };
+        if let Some(op_type) = flip {
+            let flipped = Op {
+                op_type: op_type.clone(),
+                arg: ops[ip].arg,
+            };
+            if Cfg::successor(&flipped, ip, ops.len()).is_some_and(|n| cfg.terminates[n]) {
+                repairs.push(Repair { ip, op_type });
+            }
+        }
+        match cfg.next[ip] {
+            Some(n) => ip = n,
+            None => break,
+        }
+    }
+    if ip == ops.len() {
+        // Already terminates.
+        return Ok(vec![]);
+    }
+    Ok(repairs)
+}
+
+// Problem found by analyze(), covering instructions start..end.
+#[derive(Debug, PartialEq)]
+struct Lint {
+    start: usize,
+    end: usize,
+    kind: LintKind,
+}
+
+#[derive(Debug, PartialEq)]
+enum LintKind {
+    // No way to get here from the first instruction.
+    Unreachable,
+    // Jumps or calls to given address, which isn't in the program (or its end).
+    JumpOutOfBounds(i64),
+    // Jumps or calls to itself, e.g., jmp +0.
+    JumpToSelf,
+    // Reachable, but no path from here gets to the end of the program.
+    NeverTerminates,
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.end - self.start == 1 {
+            write!(f, "{}: ", self.start)?;
+        } else {
+            write!(f, "{}-{}: ", self.start, self.end - 1)?;
+        }
+        match self.kind {
+            LintKind::Unreachable => write!(f, "unreachable"),
+            LintKind::JumpOutOfBounds(to) => write!(f, "jump out of bounds to {}", to),
+            LintKind::JumpToSelf => write!(f, "jump to itself"),
+            LintKind::NeverTerminates => write!(f, "never gets to the end of the program"),
+        }
+    }
+}
+
+// Straight run of instructions start..end that's only entered at start.
+#[derive(Debug, PartialEq)]
+struct BasicBlock {
+    start: usize,
+    end: usize,
+    // Blocks that execution can continue in, None for the end of the program.
+    next: Vec<Option<usize>>,
+}
+
+// Result of looking at a program without running it. Conditional jumps are assumed to go either
+// way, and ret to go to any call's return address or the end of the program.
+#[derive(Debug)]
+struct Analysis {
+    blocks: Vec<BasicBlock>,
+    lints: Vec<Lint>,
+}
+
+// Addresses that execution can continue at after the instruction at ip. These can be out of
+// bounds; ops.len() is the end of the program.
+fn possible_successors(ops: &[Op], ip: usize) -> Vec<i64> {
+    use OpType::*;
+
+    let here = ip as i64;
+    let to = here + ops[ip].arg as i64;
+    match ops[ip].op_type {
+        Jmp => vec![to],
+        Jcc(..) => vec![here + 1, to],
+        // Execution comes back after the call through a ret.
+        Call => vec![to, here + 1],
+        Ret => ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| op.op_type == Call)
+            .map(|(i, _)| i as i64 + 1)
+            .chain(std::iter::once(ops.len() as i64))
+            .collect(),
+        _ => vec![here + 1],
+    }
+}
+
+fn analyze(ops: &[Op]) -> Analysis {
+    use OpType::*;
+
+    let len = ops.len();
+    let in_bounds = |to: i64| to >= 0 && to <= len as i64;
+    let mut lints = vec![];
+
+    // Jumps that go nowhere useful.
+    for (ip, op) in ops.iter().enumerate() {
+        if !matches!(op.op_type, Jmp | Jcc(..) | Call) {
+            continue;
+        }
+        let to = ip as i64 + op.arg as i64;
+        let kind = if !in_bounds(to) {
+            LintKind::JumpOutOfBounds(to)
+        } else if to == ip as i64 {
+            LintKind::JumpToSelf
+        } else {
+            continue;
+        };
+        lints.push(Lint {
+            start: ip,
+            end: ip + 1,
+            kind,
+        });
+    }
+
+    // Basic blocks start at the beginning, at jump targets and after jumps.
+    let mut leader = vec![false; len + 1];
+    leader[0] = true;
+    for ip in 0..len {
+        if !matches!(
+            ops[ip].op_type,
+            Acc | Nop | Set(..) | Add(..) | Sub(..) | Mul(..)
+        ) {
+            leader[ip + 1] = true;
+            for to in possible_successors(ops, ip) {
+                if in_bounds(to) {
+                    leader[to as usize] = true;
+                }
+            }
+        }
+    }
+    let starts = (0..len).filter(|&ip| leader[ip]).collect::<Vec<usize>>();
+    let mut block_of = vec![0; len];
+    for (b, &start) in starts.iter().enumerate() {
+        let end = starts.get(b + 1).cloned().unwrap_or(len);
+        for block in block_of[start..end].iter_mut() {
+            *block = b;
+        }
+    }
+    let blocks = starts
+        .iter()
+        .enumerate()
+        .map(|(b, &start)| {
+            let end = starts.get(b + 1).cloned().unwrap_or(len);
+            let mut next = possible_successors(ops, end - 1)
+                .into_iter()
+                .filter(|&to| in_bounds(to))
+                .map(|to| block_of.get(to as usize).cloned())
+                .collect::<Vec<Option<usize>>>();
+            next.dedup();
+            BasicBlock { start, end, next }
+        })
+        .collect::<Vec<BasicBlock>>();
+
+    // Blocks reachable from the start.
+    let mut reachable = vec![false; blocks.len()];
+    let mut queue = vec![];
+    if !blocks.is_empty() {
+        reachable[0] = true;
+        queue.push(0);
+    }
+    while let Some(b) = queue.pop() {
+        for &n in blocks[b].next.iter().flatten() {
+            if !reachable[n] {
+                reachable[n] = true;
+                queue.push(n);
+            }
+        }
+    }
+
+    // Blocks that have a path to the end of the program.
+    let mut preds = vec![vec![]; blocks.len()];
+    let mut terminates = vec![false; blocks.len()];
+    let mut queue = vec![];
+    for (b, block) in blocks.iter().enumerate() {
+        for n in block.next.iter() {
+            match n {
+                Some(n) => preds[*n].push(b),
+                None if !terminates[b] => {
+                    terminates[b] = true;
+                    queue.push(b);
                 }
+                None => {}
+            }
+        }
+    }
+    while let Some(b) = queue.pop() {
+        for &p in preds[b].iter() {
+            if !terminates[p] {
+                terminates[p] = true;
+                queue.push(p);
+            }
+        }
+    }
+
+    // One lint for each run of neighbouring blocks with the same problem.
+    let mut block_lints: Vec<Lint> = vec![];
+    for (b, block) in blocks.iter().enumerate() {
+        let kind = if !reachable[b] {
+            LintKind::Unreachable
+        } else if !terminates[b] {
+            LintKind::NeverTerminates
+        } else {
+            continue;
+        };
+        match block_lints.last_mut() {
+            Some(last) if last.end == block.start && last.kind == kind => last.end = block.end,
+            _ => block_lints.push(Lint {
+                start: block.start,
+                end: block.end,
+                kind,
+            }),
+        }
+    }
+    lints.extend(block_lints);
+    lints.sort_by_key(|l| l.start);
+    Analysis { blocks, lints }
+}
+
+impl Analysis {
+    // Lists basic blocks with their instructions and where they go next:
+    //
+    // block 0 (0-1) -> 1, end
+    //      0 acc +1
+    //      1 jz acc +3
+    fn blocks_to_string(&self, ops: &[Op]) -> String {
+        let mut out = String::new();
+        for (b, block) in self.blocks.iter().enumerate() {
+            let next = block
+                .next
+                .iter()
+                .map(|n| match n {
+                    Some(n) => n.to_string(),
+                    None => String::from("end"),
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            out.push_str(&format!(
+                "block {} ({}-{}) -> {}\n",
+                b,
+                block.start,
+                block.end - 1,
+                next
+            ));
+            for (ip, op) in ops.iter().enumerate().take(block.end).skip(block.start) {
+                out.push_str(&format!("    {:4} {}\n", ip, op));
+            }
+        }
+        out
+    }
+}
+
+// One executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+struct TraceEntry {
+    // 0-based.
+    step: usize,
+    ip: ArgumentType,
+    op: Op,
+    acc_before: ArgumentType,
+    acc_after: ArgumentType,
+}
+
+// Record of a run, one entry per executed instruction. Text format is one tab-separated line per
+// entry: step, ip, acc before, acc after, instruction.
+#[derive(Debug, Clone, PartialEq)]
+struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\n",
+                    e.step, e.ip, e.acc_before, e.acc_after, e.op
+                )
+            })
+            .collect()
+    }
+
+    fn parse(text: &str) -> Result<Trace, AsmError> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let error = |message: String| AsmError {
+                    line: i + 1,
+                    message,
+                };
+                let fields = line.split('\t').collect::<Vec<&str>>();
+                if fields.len() != 5 {
+                    return Err(error(String::from("expected 5 tab-separated fields")));
+                }
+                let number = |s: &str| parse_number(s).map_err(error);
+                Ok(TraceEntry {
+                    step: fields[0]
+                        .parse::<usize>()
+                        .map_err(|_| error(format!("bad step {:?}", fields[0])))?,
+                    ip: number(fields[1])?,
+                    acc_before: number(fields[2])?,
+                    acc_after: number(fields[3])?,
+                    op: Op::parse(fields[4]).map_err(error)?,
+                })
+            })
+            .collect::<Result<Vec<TraceEntry>, AsmError>>()?;
+        Ok(Trace { entries })
+    }
+
+    // Finds the first step where two runs differ (in ip, instruction or acc), or where one of them
+    // ends before the other. None if they are the same.
+    fn diverges_from(&self, other: &Trace) -> Option<usize> {
+        let same = self
+            .entries
+            .iter()
+            .zip(other.entries.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if same == self.entries.len() && same == other.entries.len() {
+            None
+        } else {
+            Some(same)
+        }
+    }
+}
+
+impl Op {
+    // Parses a single instruction like "acc +17".
+    fn parse(line: &str) -> Result<Op, String> {
+        match assemble(line) {
+            Ok(ops) if ops.len() == 1 => Ok(ops[0].clone()),
+            Ok(_) => Err(format!("expected one instruction: {:?}", line)),
+            Err(e) => Err(e.message),
+        }
+    }
+}
+
+impl Handheld {
+    // Runs until about to execute an instruction for the second time, the end of the program or
+    // ip going out of bounds, recording every step.
+    fn record(&mut self) -> Trace {
+        let mut entries = vec![];
+        while !self.terminated() && !self.seen.contains(&self.ip) {
+            let ip = self.ip;
+            let acc_before = self.acc();
+            if self.try_step(OverflowPolicy::Wrap).is_err() {
+                break;
+            }
+            entries.push(TraceEntry {
+                step: entries.len(),
+                ip,
+                op: self.instructions[ip as usize].clone(),
+                acc_before,
+                acc_after: self.acc(),
+            });
+        }
+        Trace { entries }
+    }
+
+    // Saves the whole machine (state and program) as text that load() reads:
+    //
+    // ip 5
+    // regs 3 0 0 0 0 0 0 0
+    // stack 2
+    // seen 0 1 4
+    // program
+    // acc +3
+    // ...
+    fn save(&self) -> String {
+        let join = |v: &[ArgumentType]| v.iter().map(|x| format!(" {}", x)).collect::<String>();
+        let mut seen = self.seen.iter().cloned().collect::<Vec<ArgumentType>>();
+        seen.sort_unstable();
+        format!(
+            "ip {}\nregs{}\nstack{}\nseen{}\nprogram\n{}",
+            self.ip,
+            join(&self.regs),
+            join(&self.stack),
+            join(&seen),
+            disassemble(&self.instructions)
+        )
+    }
+
+    fn load(text: &str) -> Result<Handheld, String> {
+        let mut lines = text.lines();
+        let mut field = |name: &str| -> Result<Vec<ArgumentType>, String> {
+            let line = lines.next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            if words.next() != Some(name) {
+                return Err(format!("expected {:?}, got {:?}", name, line));
+            }
+            words.map(parse_number).collect()
+        };
+        let ip = field("ip")?;
+        let regs = field("regs")?;
+        let stack = field("stack")?;
+        let seen = field("seen")?;
+        field("program")?;
+        if ip.len() != 1 || regs.len() != REGISTER_NAMES.len() {
+            return Err(String::from("bad ip or registers"));
+        }
+        let program = lines.collect::<Vec<&str>>().join("\n");
+        let mut hh = Handheld::parse(&program).map_err(|e| format!("program {}", e))?;
+        hh.ip = ip[0];
+        hh.regs.copy_from_slice(&regs);
+        hh.stack = stack;
+        hh.seen = seen.into_iter().collect();
+        Ok(hh)
+    }
+}
+
+// Value that a debugger expression refers to.
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Num(i64),
+    // Name of a machine variable, e.g., "ip" or "acc".
+    Var(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// Debugger expression: a term, optionally combined with another one, e.g., "acc", "ip + 1" or
+// "acc >= 100". Comparisons evaluate to 1 (true) or 0 (false).
+#[derive(Debug, Clone, PartialEq)]
+struct Expr {
+    lhs: Term,
+    rhs: Option<(BinOp, Term)>,
+}
+
+impl Expr {
+    fn parse(s: &str) -> Result<Expr, String> {
+        // Split into tokens: words, numbers and operators. A '-' right before a digit is the sign
+        // of a number unless it follows a term, as in "acc < -5" but not "acc -5".
+        let mut tokens: Vec<String> = vec![];
+        let chars = s.chars().collect::<Vec<char>>();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let len = if c.is_whitespace() {
+                i += 1;
+                continue;
+            } else if c.is_alphanumeric() {
+                chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric())
+                    .count()
+            } else if c == '-'
+                && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+                && tokens
+                    .last()
+                    .is_none_or(|t| !t.starts_with(char::is_alphanumeric))
+            {
+                1 + chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric())
+                    .count()
+            } else if chars.get(i + 1) == Some(&'=') {
+                2
+            } else {
+                1
+            };
+            tokens.push(chars[i..i + len].iter().collect::<String>());
+            i += len;
+        }
+
+        let term = |t: &str| -> Result<Term, String> {
+            if let Ok(n) = t.parse::<i64>() {
+                Ok(Term::Num(n))
+            } else if t.chars().all(|c| c.is_alphabetic()) {
+                Ok(Term::Var(String::from(t)))
+            } else {
+                Err(format!("bad term {:?}", t))
+            }
+        };
+        let op = |t: &str| -> Result<BinOp, String> {
+            Ok(match t {
+                "+" => BinOp::Add,
+                "-" => BinOp::Sub,
+                "*" => BinOp::Mul,
+                "==" => BinOp::Eq,
+                "!=" => BinOp::Ne,
+                "<" => BinOp::Lt,
+                "<=" => BinOp::Le,
+                ">" => BinOp::Gt,
+                ">=" => BinOp::Ge,
+                _ => return Err(format!("bad operator {:?}", t)),
+            })
+        };
+        match tokens.as_slice() {
+            [a] => Ok(Expr {
+                lhs: term(a)?,
+                rhs: None,
+            }),
+            [a, o, b] => Ok(Expr {
+                lhs: term(a)?,
+                rhs: Some((op(o)?, term(b)?)),
+            }),
+            _ => Err(format!("can't parse expression {:?}", s)),
+        }
+    }
+
+    fn eval(&self, hh: &Handheld) -> Result<i64, String> {
+        let term = |t: &Term| match t {
+            Term::Num(n) => Ok(*n),
+            Term::Var(name) => hh
+                .var(name)
+                .ok_or_else(|| format!("unknown variable {:?}", name)),
+        };
+        let a = term(&self.lhs)?;
+        let (op, b) = match &self.rhs {
+            Some((op, t)) => (op, term(t)?),
+            None => return Ok(a),
+        };
+        Ok(match op {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::Eq => (a == b) as i64,
+            BinOp::Ne => (a != b) as i64,
+            BinOp::Lt => (a < b) as i64,
+            BinOp::Le => (a <= b) as i64,
+            BinOp::Gt => (a > b) as i64,
+            BinOp::Ge => (a >= b) as i64,
+        })
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let term = |t: &Term| match t {
+            Term::Num(n) => n.to_string(),
+            Term::Var(name) => name.clone(),
+        };
+        write!(f, "{}", term(&self.lhs))?;
+        if let Some((op, t)) = &self.rhs {
+            let op = match op {
+                BinOp::Add => "+",
+                BinOp::Sub => "-",
+                BinOp::Mul => "*",
+                BinOp::Eq => "==",
+                BinOp::Ne => "!=",
+                BinOp::Lt => "<",
+                BinOp::Le => "<=",
+                BinOp::Gt => ">",
+                BinOp::Ge => ">=",
+            };
+            write!(f, " {} {}", op, term(t))?;
+        }
+        Ok(())
+    }
+}
+
+// Where the debugger should stop.
+#[derive(Debug, Clone, PartialEq)]
+enum Breakpoint {
+    // Before executing the instruction at given ip.
+    Ip(ArgumentType),
+    // Before executing an instruction when the expression is not 0.
+    When(Expr),
+}
+
+// Machine state before one executed step, so that it can be undone.
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    ip: ArgumentType,
+    regs: [ArgumentType; REGISTER_NAMES.len()],
+    stack: Vec<ArgumentType>,
+    // Whether the step added ip to seen.
+    newly_seen: bool,
+}
+
+// Interactive debugger around a Handheld. Reads commands line by line, see HELP.
+struct Debugger {
+    hh: Handheld,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Expr>,
+    history: Vec<HistoryEntry>,
+}
+
+const HELP: &str = "\
+commands:
+  s, step [N]         execute N instructions (default 1)
+  c, continue         run until a breakpoint, a repeated instruction or the end
+  r, back [N]         undo N executed instructions (default 1)
+  b, break IP|EXPR    stop before instruction IP, or whenever EXPR is not 0 (e.g. acc > 100)
+  d, delete N         remove breakpoint N
+  breaks              list breakpoints
+  w, watch EXPR       print EXPR after every command
+  unwatch N           remove watch N
+  p, print [EXPR]     print EXPR, or the machine state
+  l, list [N]         show N instructions around ip (default 5)
+  seen                list instructions that have been executed
+  save FILE           save the machine to FILE
+  load FILE           replace the machine with one saved in FILE (forgets history)
+  h, help             show this help
+  q, quit             exit
+";
+
+impl Debugger {
+    fn new(hh: Handheld) -> Debugger {
+        Debugger {
+            hh,
+            breakpoints: vec![],
+            watches: vec![],
+            history: vec![],
+        }
+    }
+
+    // Executes one instruction, remembering how to undo it. Returns why it couldn't, if it
+    // couldn't.
+    fn step(&mut self) -> Result<(), String> {
+        let entry = HistoryEntry {
+            ip: self.hh.ip,
+            regs: self.hh.regs,
+            stack: self.hh.stack.clone(),
+            newly_seen: !self.hh.seen.contains(&self.hh.ip),
+        };
+        if let Err(status) = self.hh.try_step(OverflowPolicy::Wrap) {
+            return Err(format!("can't step: {}", status));
+        }
+        self.history.push(entry);
+        Ok(())
+    }
+
+    // Undoes the last executed instruction.
+    fn back(&mut self) -> Result<(), String> {
+        let entry = self
+            .history
+            .pop()
+            .ok_or_else(|| String::from("at the start of history"))?;
+        if entry.newly_seen {
+            self.hh.seen.remove(&entry.ip);
+        }
+        self.hh.ip = entry.ip;
+        self.hh.regs = entry.regs;
+        self.hh.stack = entry.stack;
+        Ok(())
+    }
+
+    // Returns index of a breakpoint that's hit in the current state.
+    fn hit_breakpoint(&self) -> Option<usize> {
+        self.breakpoints.iter().position(|b| match b {
+            Breakpoint::Ip(ip) => *ip == self.hh.ip,
+            Breakpoint::When(expr) => expr.eval(&self.hh).is_ok_and(|v| v != 0),
+        })
+    }
+
+    // Runs until something interesting happens, returns what it was.
+    fn cont(&mut self) -> String {
+        loop {
+            if let Err(e) = self.step() {
+                return e;
+            }
+            if self.hh.terminated() {
+                return String::from("program terminated");
+            }
+            if let Some(i) = self.hit_breakpoint() {
+                return format!("hit breakpoint {}", i);
+            }
+            if self.hh.seen.contains(&self.hh.ip) {
+                return String::from("about to repeat an instruction");
+            }
+        }
+    }
+
+    fn list(&self, n: usize) -> String {
+        // A loaded snapshot can have any ip, so this is worked out in i64.
+        let ip = self.hh.ip;
+        let len = self.hh.instructions.len();
+        let from = (ip as i64 - (n / 2) as i64).max(0);
+        let to = (from + n.min(len) as i64).min(len as i64);
+        let mut out = String::new();
+        for i in from as ArgumentType..to as ArgumentType {
+            let marker = if i == ip { "=>" } else { "  " };
+            let bp = if self.breakpoints.contains(&Breakpoint::Ip(i)) {
+                "*"
+            } else {
+                " "
             };
-            let mut handheld_tmp = self.clone();
-            handheld_tmp.instructions[i].op_type = new_op;
-            if handheld_tmp.run_until_end() {
-                return handheld_tmp.acc;
+            out.push_str(&format!(
+                "{}{} {:4} {}\n",
+                marker, bp, i, self.hh.instructions[i as usize]
+            ));
+        }
+        out
+    }
+
+    // Executes one command line, returns what to print and whether to keep going.
+    fn command(&mut self, line: &str) -> (String, bool) {
+        let line = line.trim();
+        let (cmd, arg) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        let count = |arg: &str| -> Result<usize, String> {
+            if arg.is_empty() {
+                Ok(1)
+            } else {
+                arg.parse::<usize>()
+                    .map_err(|_| format!("bad count {:?}", arg))
+            }
+        };
+        let index = |arg: &str, len: usize| -> Result<usize, String> {
+            arg.parse::<usize>()
+                .ok()
+                .filter(|&i| i < len)
+                .ok_or_else(|| format!("no such index {:?}", arg))
+        };
+        let result = match cmd {
+            "" => Ok(String::new()),
+            "s" | "step" => count(arg).and_then(|n| {
+                for _ in 0..n {
+                    self.step()?;
+                }
+                Ok(self.hh.pretty_print_state())
+            }),
+            "c" | "continue" => {
+                let why = self.cont();
+                Ok(format!("{}\n{}", why, self.hh.pretty_print_state()))
+            }
+            "r" | "back" => count(arg).and_then(|n| {
+                for _ in 0..n {
+                    self.back()?;
+                }
+                Ok(self.hh.pretty_print_state())
+            }),
+            "b" | "break" => {
+                let bp = match arg.parse::<ArgumentType>() {
+                    Ok(ip) => Ok(Breakpoint::Ip(ip)),
+                    Err(_) => Expr::parse(arg).map(Breakpoint::When),
+                };
+                bp.map(|bp| {
+                    self.breakpoints.push(bp);
+                    format!("breakpoint {}", self.breakpoints.len() - 1)
+                })
+            }
+            "d" | "delete" => index(arg, self.breakpoints.len()).map(|i| {
+                self.breakpoints.remove(i);
+                String::new()
+            }),
+            "breaks" => Ok(self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(i, b)| match b {
+                    Breakpoint::Ip(ip) => format!("{}: ip {}\n", i, ip),
+                    Breakpoint::When(expr) => format!("{}: when {}\n", i, expr),
+                })
+                .collect()),
+            "w" | "watch" => Expr::parse(arg).map(|expr| {
+                self.watches.push(expr);
+                format!("watch {}", self.watches.len() - 1)
+            }),
+            "unwatch" => index(arg, self.watches.len()).map(|i| {
+                self.watches.remove(i);
+                String::new()
+            }),
+            "p" | "print" if arg.is_empty() => Ok(self.hh.pretty_print_state()),
+            "p" | "print" => Expr::parse(arg)
+                .and_then(|expr| expr.eval(&self.hh))
+                .map(|v| v.to_string()),
+            "l" | "list" => {
+                let n = if arg.is_empty() { Ok(5) } else { count(arg) };
+                n.map(|n| self.list(n))
+            }
+            "seen" => {
+                let mut seen = self.hh.seen.iter().cloned().collect::<Vec<ArgumentType>>();
+                seen.sort_unstable();
+                Ok(format!("{:?}", seen))
+            }
+            "save" => std::fs::write(arg, self.hh.save())
+                .map(|_| format!("saved to {}", arg))
+                .map_err(|e| e.to_string()),
+            "load" => std::fs::read_to_string(arg)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Handheld::load(&text))
+                .map(|hh| {
+                    self.hh = hh;
+                    self.history.clear();
+                    self.hh.pretty_print_state()
+                }),
+            "h" | "help" => Ok(String::from(HELP)),
+            "q" | "quit" => return (String::new(), false),
+            _ => Err(format!("unknown command {:?}, try \"help\"", cmd)),
+        };
+        let mut out = match result {
+            Ok(s) => s,
+            Err(e) => format!("error: {}", e),
+        };
+        for (i, expr) in self.watches.iter().enumerate() {
+            let value = match expr.eval(&self.hh) {
+                Ok(v) => v.to_string(),
+                Err(e) => format!("error: {}", e),
+            };
+            out.push_str(&format!("\nwatch {}: {} = {}", i, expr, value));
+        }
+        (out, true)
+    }
+
+    fn run(&mut self, input: impl std::io::BufRead, mut output: impl std::io::Write) {
+        writeln!(output, "{}", self.hh.pretty_print_state()).unwrap();
+        for line in input.lines() {
+            let (out, keep_going) = self.command(&line.expect("read failed"));
+            if !keep_going {
+                break;
+            }
+            if !out.is_empty() {
+                writeln!(output, "{}", out.trim_end()).unwrap();
             }
         }
-        panic!("sad panda");
     }
 }
 
+// Parses "name=value" limits for the run mode of main().
+fn parse_limits(args: impl Iterator<Item = String>) -> Result<Limits, String> {
+    let mut limits = Limits::default();
+    for arg in args {
+        let (name, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=VALUE: {}", arg))?;
+        let bad_value = || format!("bad value for {}: {}", name, value);
+        match name {
+            "steps" => limits.max_steps = Some(value.parse().map_err(|_| bad_value())?),
+            "ms" => {
+                let ms = value.parse().map_err(|_| bad_value())?;
+                limits.max_time = Some(std::time::Duration::from_millis(ms));
+            }
+            "stack" => limits.max_stack = Some(value.parse().map_err(|_| bad_value())?),
+            "overflow" => {
+                limits.overflow = match value {
+                    "wrap" => OverflowPolicy::Wrap,
+                    "saturate" => OverflowPolicy::Saturate,
+                    "error" => OverflowPolicy::Error,
+                    _ => return Err(bad_value()),
+                }
+            }
+            "repeat" => {
+                limits.stop_on_repeat = match value {
+                    "stop" => true,
+                    "go" => false,
+                    _ => return Err(bad_value()),
+                }
+            }
+            _ => return Err(format!("unknown limit: {}", name)),
+        }
+    }
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMPLE: &str = "\
+nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+
+    #[test]
+    fn old_programs_round_trip() {
+        assert_eq!(disassemble(&assemble(SIMPLE).unwrap()), SIMPLE);
+        let mut hh = Handheld::parse(SIMPLE).unwrap();
+        assert_eq!(hh.run(&Limits::default()), (RunStatus::Looped(1), 7));
+        assert_eq!(hh.acc(), 5);
+    }
+
+    #[test]
+    fn extended_programs_run() {
+        // Computes 5! into acc with a call, a loop and several registers.
+        let source = "\
+            set r1 5          ; n
+            call factorial
+            jmp end
+        factorial:
+            set acc 1
+        loop: mul acc r1
+            sub r1 1
+            jgz r1 loop
+            ret
+        end:
+        ";
+        let ops = assemble(source).unwrap();
+        assert_eq!(
+            disassemble(&ops),
+            "set r1 5\ncall +2\njmp +6\nset acc 1\nmul acc r1\nsub r1 1\njgz r1 -2\nret\n"
+        );
+        assert_eq!(assemble(&disassemble(&ops)).unwrap(), ops);
+        let mut hh = Handheld::new(ops);
+        let limits = Limits {
+            stop_on_repeat: false,
+            ..Limits::default()
+        };
+        assert_eq!(hh.run(&limits), (RunStatus::Terminated, 20));
+        assert_eq!(hh.acc(), 120);
+        assert_eq!(hh.var("r1"), Some(0));
+    }
+
+    #[test]
+    fn assemble_reports_errors() {
+        let error = |source| assemble(source).unwrap_err();
+        assert_eq!(error("nop +0\njmp nowhere").line, 2);
+        assert_eq!(error("add r9 1").message, "unknown register \"r9\"");
+        assert_eq!(error("a:\na: nop +0").message, "label \"a\" defined twice");
+        assert_eq!(error("acc").line, 1);
+        let message = "label \"2\" looks like a number or register";
+        assert_eq!(error("jmp 2\n2: acc +1").message, message);
+        assert_eq!(error("r1: nop +0").line, 1);
+    }
+
+    #[test]
+    fn find_repairs_works() {
+        let mut hh = Handheld::parse(SIMPLE).unwrap();
+        let fixes = hh.find_broken_instr().unwrap();
+        assert_eq!(
+            fixes,
+            vec![(
+                Repair {
+                    ip: 7,
+                    op_type: OpType::Nop
+                },
+                8
+            )]
+        );
+    }
+
+    #[test]
+    fn find_repairs_lists_every_candidate() {
+        // Only the last jmp can go: turning the first one into a nop loops through the second.
+        let ops = assemble("jmp +2\njmp -1\njmp -1\n").unwrap();
+        let ips = |ops: &[Op]| {
+            find_repairs(ops)
+                .unwrap()
+                .iter()
+                .map(|r| r.ip)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(ips(&ops), vec![2]);
+        // Jumping over the jmp +0 or turning it into a nop both work.
+        let ops = assemble("nop +2\njmp +0\nacc +1\n").unwrap();
+        assert_eq!(ips(&ops), vec![0, 1]);
+        assert_eq!(ips(&assemble("nop +0\n").unwrap()), vec![]);
+        assert!(find_repairs(&assemble("jz acc +0").unwrap()).is_err());
+    }
+
+    #[test]
+    fn analyze_finds_problems() {
+        let ops = assemble(
+            "\
+            jz acc skip
+            jmp +0
+        skip:
+            call sub
+            jmp +10
+            acc +1
+        sub:
+            ret
+        ",
+        )
+        .unwrap();
+        let analysis = analyze(&ops);
+        assert_eq!(
+            analysis.lints,
+            vec![
+                Lint {
+                    start: 1,
+                    end: 2,
+                    kind: LintKind::JumpToSelf
+                },
+                Lint {
+                    start: 1,
+                    end: 2,
+                    kind: LintKind::NeverTerminates
+                },
+                Lint {
+                    start: 3,
+                    end: 4,
+                    kind: LintKind::JumpOutOfBounds(13)
+                },
+                Lint {
+                    start: 3,
+                    end: 4,
+                    kind: LintKind::NeverTerminates
+                },
+                Lint {
+                    start: 4,
+                    end: 5,
+                    kind: LintKind::Unreachable
+                },
+            ]
+        );
+        assert_eq!(
+            analysis.blocks[0],
+            BasicBlock {
+                start: 0,
+                end: 1,
+                next: vec![Some(1), Some(2)]
+            }
+        );
+        assert!(analyze(&assemble(SIMPLE).unwrap())
+            .lints
+            .iter()
+            .any(|l| l.kind == LintKind::NeverTerminates));
+    }
+
+    #[test]
+    fn traces_round_trip_and_diverge() {
+        let mut hh = Handheld::parse(SIMPLE).unwrap();
+        let trace = hh.record();
+        assert_eq!(trace.entries.len(), 7);
+        assert_eq!(
+            trace.entries[1],
+            TraceEntry {
+                step: 1,
+                ip: 1,
+                op: Op::parse("acc +1").unwrap(),
+                acc_before: 0,
+                acc_after: 1,
+            }
+        );
+        assert_eq!(Trace::parse(&trace.to_text()).unwrap(), trace);
+
+        let mut patched = Handheld::parse(SIMPLE).unwrap();
+        patched.instructions[7].op_type = OpType::Nop;
+        let patched_trace = patched.record();
+        assert_eq!(trace.diverges_from(&trace), None);
+        assert_eq!(trace.diverges_from(&patched_trace), Some(4));
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let mut hh = Handheld::parse("set r2 -4\ncall +2\nacc +1\nret\n").unwrap();
+        hh.try_step(OverflowPolicy::Wrap).unwrap();
+        hh.try_step(OverflowPolicy::Wrap).unwrap();
+        let loaded = Handheld::load(&hh.save()).unwrap();
+        assert_eq!(loaded.ip, 3);
+        assert_eq!(loaded.regs, hh.regs);
+        assert_eq!(loaded.stack, vec![2]);
+        assert_eq!(loaded.seen, hh.seen);
+        assert_eq!(loaded.instructions, hh.instructions);
+        assert!(Handheld::load("ip 0\n").is_err());
+    }
+
+    #[test]
+    fn runs_stop_at_limits() {
+        let forever = Limits {
+            stop_on_repeat: false,
+            ..Limits::default()
+        };
+        let mut hh = Handheld::parse("jmp +0\n").unwrap();
+        let limits = Limits {
+            max_steps: Some(100),
+            ..forever.clone()
+        };
+        assert_eq!(hh.run(&limits), (RunStatus::StepLimit, 100));
+        let mut hh = Handheld::parse("call +0\n").unwrap();
+        let limits = Limits {
+            max_stack: Some(10),
+            ..forever.clone()
+        };
+        assert_eq!(hh.run(&limits), (RunStatus::StackLimit, 10));
+        assert_eq!(hh.stack.len(), 10);
+        let mut hh = Handheld::parse("nop +0\njmp -5\n").unwrap();
+        assert_eq!(hh.run(&forever), (RunStatus::OutOfBounds(-4), 2));
+        assert_eq!(hh.run(&forever), (RunStatus::OutOfBounds(-4), 0));
+        // The target is reported even if it doesn't fit into ip.
+        let mut hh = Handheld::parse("nop +0\njmp +2147483647\n").unwrap();
+        assert_eq!(
+            hh.run(&forever),
+            (RunStatus::OutOfBounds(1 + ArgumentType::MAX as i64), 1)
+        );
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let source = "acc +2147483647\nacc +1\n";
+        let run = |overflow| {
+            let mut hh = Handheld::parse(source).unwrap();
+            let limits = Limits {
+                overflow,
+                ..Limits::default()
+            };
+            (hh.run(&limits).0, hh.ip, hh.acc())
+        };
+        use OverflowPolicy::*;
+        assert_eq!(run(Wrap), (RunStatus::Terminated, 2, i32::MIN));
+        assert_eq!(run(Saturate), (RunStatus::Terminated, 2, i32::MAX));
+        assert_eq!(run(Error), (RunStatus::Overflow(1), 1, i32::MAX));
+    }
+
+    #[test]
+    fn debugger_steps_back_and_forth() {
+        let mut dbg = Debugger::new(Handheld::parse(SIMPLE).unwrap());
+        dbg.command("step 3");
+        assert_eq!((dbg.hh.ip, dbg.hh.acc()), (6, 1));
+        dbg.command("back 2");
+        assert_eq!((dbg.hh.ip, dbg.hh.acc()), (1, 0));
+        assert_eq!(dbg.hh.seen.len(), 1);
+        let (out, _) = dbg.command("back 2");
+        assert!(out.starts_with("error: at the start of history"));
+        assert_eq!(dbg.hh.ip, 0);
+    }
+
+    #[test]
+    fn debugger_breakpoints_and_watches() {
+        let mut dbg = Debugger::new(Handheld::parse(SIMPLE).unwrap());
+        dbg.command("break acc >= 2");
+        dbg.command("watch ip * 10");
+        let (out, _) = dbg.command("continue");
+        assert!(out.starts_with("hit breakpoint 0"));
+        assert!(out.ends_with("watch 0: ip * 10 = 70"));
+        assert_eq!(dbg.hh.acc(), 2);
+        dbg.command("delete 0");
+        let (out, _) = dbg.command("c");
+        assert!(out.starts_with("about to repeat an instruction"));
+        assert_eq!(dbg.hh.acc(), 5);
+        let (out, _) = dbg.command("seen");
+        assert!(out.starts_with("[0, 1, 2, 3, 4, 6, 7]"));
+        let (_, keep_going) = dbg.command("quit");
+        assert!(!keep_going);
+    }
+
+    #[test]
+    fn debugger_lists_around_any_ip() {
+        let mut dbg = Debugger::new(Handheld::parse(SIMPLE).unwrap());
+        dbg.hh.ip = ArgumentType::MIN;
+        assert_eq!(dbg.list(4).lines().count(), 4);
+        dbg.hh.ip = ArgumentType::MAX;
+        assert_eq!(dbg.list(4).lines().count(), 0);
+        dbg.hh.ip = 1;
+        let listing = dbg.list(usize::MAX);
+        assert_eq!(listing.lines().count(), 9);
+        assert!(listing.lines().nth(1).unwrap().starts_with("=>"));
+    }
+
+    #[test]
+    fn debugger_takes_negative_numbers() {
+        let program = "acc -2\nacc -2\nacc -2\nacc -2";
+        let mut dbg = Debugger::new(Handheld::parse(program).unwrap());
+        dbg.command("break acc < -5");
+        let (out, _) = dbg.command("continue");
+        assert!(out.starts_with("hit breakpoint 0"));
+        assert_eq!(dbg.hh.acc(), -6);
+        assert_eq!(dbg.command("print acc - -1").0.trim_end(), "-5");
+        assert_eq!(dbg.command("print -1-acc").0.trim_end(), "5");
+        assert_eq!(
+            Expr::parse("acc-1"),
+            Ok(Expr {
+                lhs: Term::Var(String::from("acc")),
+                rhs: Some((BinOp::Sub, Term::Num(1))),
+            })
+        );
+    }
+}
+
+// Prints where two runs go different ways, with a few steps of context.
+fn print_divergence(a: &Trace, b: &Trace) {
+    let step = match a.diverges_from(b) {
+        Some(step) => step,
+        None => {
+            println!("runs are the same ({} steps)", a.entries.len());
+            return;
+        }
+    };
+    println!("runs diverge at step {}", step);
+    for (name, trace) in [("a", a), ("b", b)].iter() {
+        let from = step.saturating_sub(3);
+        for e in trace.entries.iter().skip(from).take(step - from + 3) {
+            let marker = if e.step == step { ">" } else { " " };
+            println!(
+                "{}{} {}",
+                marker,
+                name,
+                Trace {
+                    entries: vec![e.clone()]
+                }
+                .to_text()
+                .trim_end()
+            );
+        }
+        if trace.entries.len() <= step {
+            println!(">{} ends after {} steps", name, trace.entries.len());
+        }
+    }
+}
+
+// Usage: day8 [FILE [debug | disasm | lint | trace | replay TRACE | diff-fix | run [LIMIT...]]]
+//
+// * debug: start an interactive debugger reading commands from stdin.
+// * disasm: print the program back out without labels and comments.
+// * lint: check the program without running it and list its basic blocks. Exits with 1 if
+//   there are problems.
+// * trace: run until an instruction repeats or the program ends and print the trace.
+// * replay TRACE: run again and compare with a trace printed by "trace" earlier.
+// * diff-fix: compare runs of the program before and after the first repair.
+// * run [LIMIT...]: run the program as is and print why it stopped. LIMITs are steps=N, ms=N,
+//   stack=N, overflow=wrap|saturate|error and repeat=stop|go (repeat=go with no other limits
+//   may run forever).
 fn main() {
-    let contents = std::fs::read_to_string("input/8.txt").expect("read failed");
-    let mut hh = Handheld::parse(&contents);
-    dbg!(hh.find_broken_instr());
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/8.txt"));
+    let contents = std::fs::read_to_string(path).expect("read failed");
+    let mut hh = match Handheld::parse(&contents) {
+        Ok(hh) => hh,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match std::env::args().nth(2).as_deref() {
+        Some("debug") => {
+            let stdin = std::io::stdin();
+            Debugger::new(hh).run(stdin.lock(), std::io::stdout());
+            return;
+        }
+        Some("disasm") => {
+            print!("{}", disassemble(&hh.instructions));
+            return;
+        }
+        Some("lint") => {
+            let analysis = analyze(&hh.instructions);
+            print!("{}", analysis.blocks_to_string(&hh.instructions));
+            for lint in analysis.lints.iter() {
+                println!("{}", lint);
+            }
+            if !analysis.lints.is_empty() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("trace") => {
+            print!("{}", hh.record().to_text());
+            return;
+        }
+        Some("replay") => {
+            let path = std::env::args().nth(3).expect("no trace");
+            let text = std::fs::read_to_string(path).expect("read failed");
+            let old = Trace::parse(&text).unwrap_or_else(|e| panic!("{}", e));
+            print_divergence(&old, &hh.record());
+            return;
+        }
+        Some("diff-fix") => {
+            let repair = find_repairs(&hh.instructions)
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                })
+                .into_iter()
+                .next()
+                .expect("nothing to fix");
+            let mut patched = hh.clone();
+            patched.instructions[repair.ip].op_type = repair.op_type;
+            print_divergence(&hh.record(), &patched.record());
+            return;
+        }
+        Some("run") => {
+            let limits = parse_limits(std::env::args().skip(3)).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let (status, steps) = hh.run(&limits);
+            println!("{} after {} steps", status, steps);
+            println!("{}", hh.pretty_print_state());
+            return;
+        }
+        _ => {}
+    }
+    let fixes = hh.find_broken_instr().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    for (repair, acc) in fixes {
+        println!(
+            "fix: {} at {} -> {}, acc: {}",
+            hh.instructions[repair.ip],
+            repair.ip,
+            Op {
+                op_type: repair.op_type,
+                arg: hh.instructions[repair.ip].arg
+            },
+            acc
+        );
+    }
     // dbg!(&hh.ip);
-    // hh.run_until_repeats();
+    // hh.run(&Limits::default());
     // dbg!(hh.pretty_print_state());
-    // hh.run_until_end();
-    // println!("{}", hh.pretty_print_state());
 }
@@ -1,53 +1,303 @@
-// Returns true if two numbers in window sum up to "sum".
-fn find_sum(sum: u64, window: &[u64]) -> bool {
-    for (i, num1) in window.iter().enumerate() {
-        for num2 in window[i + 1..].iter() {
-            if (num1 + num2) == sum {
-                return true;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::ops::Range;
+
+// A number that isn't the sum of two of the numbers in the window before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Invalid {
+    // 0-based index in the stream.
+    position: usize,
+    value: u64,
+}
+
+// Checks numbers one at a time. Keeps the sums of all pairs in the window in a multiset so
+// checking a number is O(1) and moving the window is O(window).
+struct Validator {
+    window: usize,
+    recent: VecDeque<u64>,
+    // Pair sum -> number of pairs in the window with that sum. Pairs that don't fit into a u64
+    // can't match anything and are left out.
+    pair_sums: HashMap<u64, usize>,
+    position: usize,
+}
+
+impl Validator {
+    fn new(window: usize) -> Validator {
+        Validator {
+            window,
+            recent: VecDeque::with_capacity(window + 1),
+            pair_sums: HashMap::new(),
+            position: 0,
+        }
+    }
+
+    // Checks the next number and moves the window. The first "window" numbers are the preamble
+    // and always valid.
+    fn push(&mut self, value: u64) -> Option<Invalid> {
+        let position = self.position;
+        self.position += 1;
+        let invalid = position >= self.window && !self.pair_sums.contains_key(&value);
+
+        for sum in self.recent.iter().filter_map(|n| n.checked_add(value)) {
+            *self.pair_sums.entry(sum).or_insert(0) += 1;
+        }
+        self.recent.push_back(value);
+        if self.recent.len() > self.window {
+            let old = self.recent.pop_front().unwrap();
+            for sum in self.recent.iter().filter_map(|n| n.checked_add(old)) {
+                let count = self.pair_sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.pair_sums.remove(&sum);
+                }
             }
         }
+
+        if invalid {
+            Some(Invalid { position, value })
+        } else {
+            None
+        }
     }
-    false
 }
 
-fn part1(input: &[u64]) {
-    for i in 25..input.len() {
-        let sum = input[i];
-        if !find_sum(sum, &input[i - 25..i]) {
-            dbg!(sum);
-            break;
+// Reads one number per line (blank lines are skipped) and yields the invalid ones as they are
+// found, so it works on streams that never end.
+struct InvalidNumbers<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    validator: Validator,
+    // Set after an error.
+    done: bool,
+}
+
+impl<R: BufRead> InvalidNumbers<R> {
+    fn new(reader: R, window: usize) -> InvalidNumbers<R> {
+        InvalidNumbers {
+            lines: reader.lines(),
+            line: 0,
+            validator: Validator::new(window),
+            done: false,
         }
     }
 }
 
-fn part2(input: &[u64]) {
-    const TO_FIND: u64 = 1930745883;
-    // const TO_FIND: u64 = 127;
+impl<R: BufRead> Iterator for InvalidNumbers<R> {
+    type Item = Result<Invalid, String>;
+
+    // Stops after the first error.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.to_string()));
+                }
+            };
+            self.line += 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let value = match line.parse::<u64>() {
+                Ok(value) => value,
+                Err(_) => {
+                    self.done = true;
+                    return Some(Err(format!("line {}: not a number: {:?}", self.line, line)));
+                }
+            };
+            if let Some(invalid) = self.validator.push(value) {
+                return Some(Ok(invalid));
+            }
+        }
+    }
+}
+
+// Finds the contiguous range of at least two numbers summing to target that ends first. Numbers
+// can't be negative, which lets this use two pointers and no extra memory.
+fn first_range_with_sum(input: &[u64], target: u64) -> Option<Range<usize>> {
+    let target = target as u128;
     let mut head = 0;
-    let mut tail = 0;
-    let mut sum = 0;
-    while sum != TO_FIND {
-        if sum < TO_FIND {
-            sum += input[tail];
-            tail += 1;
-        } else {
-            sum -= input[head];
+    let mut sum: u128 = 0;
+    for (tail, &n) in input.iter().enumerate() {
+        sum += n as u128;
+        while sum > target {
+            sum -= input[head] as u128;
             head += 1;
         }
+        // With zeros around, head is as far left as it goes, so this is the longest candidate
+        // ending here.
+        if sum == target && tail > head {
+            return Some(head..tail + 1);
+        }
+    }
+    None
+}
+
+// Finds all contiguous ranges of at least two numbers summing to target, ordered by start and
+// then end. Works with negative numbers by looking up earlier prefix sums in a map.
+fn ranges_with_sum(input: &[i64], target: i64) -> Vec<Range<usize>> {
+    // Prefix sum -> indices where it occurs. i128 so the sums can't overflow.
+    let mut prefixes: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut ranges = vec![];
+    let mut sum: i128 = 0;
+    for (i, &n) in input.iter().enumerate() {
+        // Prefix sum before input[i - 1], so ranges have at least two numbers.
+        if i >= 1 {
+            let before = sum - input[i - 1] as i128;
+            prefixes.entry(before).or_default().push(i - 1);
+        }
+        sum += n as i128;
+        if let Some(starts) = prefixes.get(&(sum - target as i128)) {
+            ranges.extend(starts.iter().map(|&start| start..i + 1));
+        }
+    }
+    ranges.sort_by_key(|r| (r.start, r.end));
+    ranges
+}
+
+// The sum of the smallest and largest number in a range.
+fn weakness(input: &[u64], range: Range<usize>) -> Option<u64> {
+    let slice = &input[range];
+    Some(slice.iter().min()? + slice.iter().max()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validator_finds_every_invalid_number() {
+        let text = std::fs::read_to_string("input/9_simple.txt").unwrap();
+        let invalid = InvalidNumbers::new(text.as_bytes(), 5)
+            .collect::<Result<Vec<Invalid>, String>>()
+            .unwrap();
+        assert_eq!(
+            invalid[0],
+            Invalid {
+                position: 14,
+                value: 127
+            }
+        );
+
+        // Equal numbers at different positions count as a pair, the number itself doesn't.
+        let mut validator = Validator::new(2);
+        let results = [3, 3, 6, 3, 9, 13]
+            .iter()
+            .map(|&n| validator.push(n).map(|i| i.position))
+            .collect::<Vec<_>>();
+        assert_eq!(results, vec![None, None, None, Some(3), None, Some(5)]);
+
+        let mut errors = InvalidNumbers::new("1\n\n2\nx\n5\n".as_bytes(), 1);
+        assert_eq!(
+            errors.next(),
+            Some(Ok(Invalid {
+                position: 1,
+                value: 2
+            }))
+        );
+        assert_eq!(
+            errors.next(),
+            Some(Err(String::from("line 4: not a number: \"x\"")))
+        );
+        // 5 would be invalid after 2, but reading stops at the error.
+        assert_eq!(errors.next(), None);
+    }
+
+    #[test]
+    fn range_search_works() {
+        let text = std::fs::read_to_string("input/9_simple.txt").unwrap();
+        let input = text
+            .lines()
+            .map(|line| line.parse::<u64>().unwrap())
+            .collect::<Vec<u64>>();
+        assert_eq!(first_range_with_sum(&input, 127), Some(2..6));
+        assert_eq!(weakness(&input, 2..6), Some(62));
+        let signed = input.iter().map(|&n| n as i64).collect::<Vec<i64>>();
+        assert_eq!(ranges_with_sum(&signed, 127), vec![2..6]);
+
+        // The number itself isn't a range, and running off the end isn't a panic.
+        assert_eq!(first_range_with_sum(&[5, 3], 5), None);
+        assert_eq!(first_range_with_sum(&[1, 2], 10), None);
+        assert_eq!(first_range_with_sum(&[], 0), None);
+        assert_eq!(first_range_with_sum(&[0, 5, 0], 5), Some(0..2));
+        assert_eq!(first_range_with_sum(&[3, 0, 0], 0), Some(1..3));
+
+        assert_eq!(
+            ranges_with_sum(&[1, -1, 1, -1], 0),
+            vec![0..2, 0..4, 1..3, 2..4]
+        );
+        assert_eq!(ranges_with_sum(&[0, 5, 0], 5), vec![0..2, 0..3, 1..3]);
+        assert_eq!(ranges_with_sum(&[5], 5), vec![]);
     }
-    dbg!(sum, head, tail);
-    let min = input[head..tail].iter().min().unwrap();
-    let max = input[head..tail].iter().max().unwrap();
-    let answer = min + max;
-    dbg!(answer);
 }
 
+// Usage: day9 [FILE [WINDOW [TARGET]]]
+//
+// FILE "-" reads numbers from stdin as they come. Prints every number that isn't the sum of two of
+// the WINDOW (default 25) numbers before it. For files, then prints every range of at least two
+// numbers summing to TARGET (default: the first invalid number) and the weakness of the first.
 fn main() {
-    let contents = std::fs::read_to_string("input/9.txt").expect("read failed");
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("input/9.txt"));
+    let window = std::env::args()
+        .nth(2)
+        .map_or(25, |w| w.parse().expect("bad window"));
+    let reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(std::io::BufReader::new(std::io::stdin()))
+    } else {
+        Box::new(std::io::BufReader::new(
+            std::fs::File::open(&path).expect("open failed"),
+        ))
+    };
+    let mut first_invalid = None;
+    for result in InvalidNumbers::new(reader, window) {
+        match result {
+            Ok(invalid) => {
+                println!("{}: {}", invalid.position, invalid.value);
+                first_invalid.get_or_insert(invalid.value);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if path == "-" {
+        return;
+    }
+    let target = match std::env::args().nth(3) {
+        Some(t) => t.parse::<u64>().expect("bad target"),
+        None => match first_invalid {
+            Some(t) => t,
+            None => return,
+        },
+    };
+    let contents = std::fs::read_to_string(path).expect("read failed");
     let input = contents
         .lines()
-        .map(|line| line.parse::<u64>().unwrap())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.trim().parse::<u64>().unwrap())
         .collect::<Vec<u64>>();
-    // part1(input.as_slice());
-    part2(input.as_slice());
+    // Listing every range needs the numbers to fit into an i64.
+    let signed = input
+        .iter()
+        .map(|&n| i64::try_from(n).ok())
+        .collect::<Option<Vec<i64>>>();
+    if let (Some(signed), Ok(target)) = (signed, i64::try_from(target)) {
+        for range in ranges_with_sum(&signed, target) {
+            println!("range {}..{} sums to {}", range.start, range.end, target);
+        }
+    }
+    match first_range_with_sum(&input, target) {
+        Some(range) => println!("weakness: {}", weakness(&input, range).unwrap()),
+        None => println!("no range sums to {}", target),
+    }
 }